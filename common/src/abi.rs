@@ -0,0 +1,151 @@
+//The host binary and the `game` library are compiled separately, and the
+//library can be rebuilt while the host is running. Nothing checks that the
+//two still agree on what the exported functions look like, and if they don't,
+//calling into the library is undefined behaviour. So the library exports its
+//`ABI_VERSION`, and the host compares it with its own before calling anything
+//else.
+//
+//`Game` is expected to change between reloads, so the host never looks inside
+//one. It only holds a `GameHandle` that the library gave it, and when a new
+//library is loaded has the old one write the game out with `save`, for the new
+//library to `load` into its own idea of `Game`.
+
+use std::any;
+use std::collections::hash_map::DefaultHasher;
+use std::hash::Hasher;
+use std::mem;
+
+use bindings::Bindings;
+use theme::Theme;
+use {Event, Game, Instruction, Platform, Seed, Size, PLAYFIELD_SIZE};
+
+/// Bump this whenever the signature of any exported function changes, or the
+/// layout of anything besides `Game` that is passed across, like `Platform`.
+pub const ABI_VERSION: u32 = 15;
+
+/// A boxed `Game`, laid out however the library that made it thinks `Game`
+/// is. Only that library may use it, which includes getting rid of it, so
+/// dropping a handle leaks the game rather than freeing it.
+pub struct GameHandle(*mut Game);
+
+impl GameHandle {
+    pub fn new(game: Game) -> Self {
+        GameHandle(Box::into_raw(Box::new(game)))
+    }
+
+    /// # Safety
+    /// `self` has to have come from `GameHandle::new` in the calling crate.
+    pub unsafe fn game(&self) -> &Game {
+        &*self.0
+    }
+
+    /// # Safety
+    /// `self` has to have come from `GameHandle::new` in the calling crate.
+    pub unsafe fn game_mut(&mut self) -> &mut Game {
+        &mut *self.0
+    }
+
+    /// # Safety
+    /// `self` has to have come from `GameHandle::new` in the calling crate.
+    pub unsafe fn into_game(self) -> Game {
+        *Box::from_raw(self.0)
+    }
+}
+
+pub type AbiVersion = fn() -> u32;
+pub type GameLayoutHash = fn() -> u64;
+pub type CreateGame = fn([Instruction; PLAYFIELD_SIZE], Size, Seed, Seed) -> GameHandle;
+/// Returns whether the player asked to quit, or the message the game panicked
+/// with, in which case the game is put back how it was before the call.
+pub type UpdateGame = fn(&Platform, &mut GameHandle, &mut Vec<Event>) -> Result<bool, String>;
+/// Writes the game out in the `save` module's format.
+pub type SaveGame = fn(&GameHandle) -> String;
+pub type LoadGame = fn(&str, Size) -> Result<GameHandle, String>;
+pub type DropGame = fn(GameHandle);
+/// Replaces the game's key bindings, like after the bindings file changes.
+pub type SetBindings = fn(&mut GameHandle, Bindings);
+/// Replaces the game's theme, like after the theme file changes.
+pub type SetTheme = fn(&mut GameHandle, Theme);
+/// Has the next update draw the whole screen, rather than only what changed,
+/// for after something else has drawn on it.
+pub type InvalidateScreen = fn(&mut GameHandle);
+
+/// The names the library exports the functions above under, nul terminated
+/// the way `libloading` wants them.
+pub const ABI_VERSION_SYMBOL: &[u8] = b"abi_version\0";
+pub const GAME_LAYOUT_HASH_SYMBOL: &[u8] = b"game_layout_hash\0";
+pub const CREATE_GAME_SYMBOL: &[u8] = b"create_game\0";
+pub const UPDATE_GAME_SYMBOL: &[u8] = b"update_game\0";
+pub const SAVE_GAME_SYMBOL: &[u8] = b"save_game\0";
+pub const LOAD_GAME_SYMBOL: &[u8] = b"load_game\0";
+pub const DROP_GAME_SYMBOL: &[u8] = b"drop_game\0";
+pub const SET_BINDINGS_SYMBOL: &[u8] = b"set_bindings\0";
+pub const SET_THEME_SYMBOL: &[u8] = b"set_theme\0";
+pub const INVALIDATE_SCREEN_SYMBOL: &[u8] = b"invalidate_screen\0";
+
+/// The type of `update_and_render` in the `game` crate, for calling it
+/// directly rather than through a `GameHandle`.
+pub type UpdateAndRender = fn(&Platform, &mut Game, &mut Vec<Event>) -> bool;
+
+//Hashes the size and alignment of the struct, and the name, offset, size and
+//type of each field. The destructuring in `all_fields_listed` makes adding a
+//field to the struct without listing it here a compile error.
+macro_rules! layout_hash {
+    ($name:ident { $($field:ident),* $(,)* }) => {{
+        #[allow(dead_code)]
+        fn all_fields_listed(value: $name) {
+            let $name { $($field: _),* } = value;
+        }
+
+        let mut hasher = DefaultHasher::new();
+        hasher.write_usize(mem::size_of::<$name>());
+        hasher.write_usize(mem::align_of::<$name>());
+        $(
+            hasher.write(stringify!($field).as_bytes());
+            hasher.write_usize(mem::offset_of!($name, $field));
+            let (size, type_name) = field_info(|value: &$name| &value.$field);
+            hasher.write_usize(size);
+            hasher.write(type_name.as_bytes());
+        )*
+        hasher.finish()
+    }};
+}
+
+fn field_info<S, F, G: Fn(&S) -> &F>(_: G) -> (usize, &'static str) {
+    (mem::size_of::<F>(), any::type_name::<F>())
+}
+
+/// A hash of how `Game` is laid out, as compiled into whichever crate calls
+/// this. Two crates that get the same hash could pass a `Game` between them,
+/// and if they get different ones the game was migrated on reload.
+pub fn game_layout_hash() -> u64 {
+    layout_hash!(Game {
+        instructions,
+        history,
+        turn,
+        player,
+        peeled_layers,
+        scroll_offset,
+        cards,
+        next_card_id,
+        selected_card,
+        layout,
+        ui_context,
+        paused,
+        executing_address,
+        instruction_countdown,
+        registers,
+        trace,
+        trace_list,
+        profile,
+        rng,
+        editor,
+        seed,
+        daily_seed,
+        new_game_screen,
+        placement_cursor,
+        bindings,
+        theme,
+        screen,
+    })
+}
@@ -0,0 +1,414 @@
+//Works out what a playfield can do without running it, by building a control
+//flow graph of which addresses execution can go to from each one. Running
+//always starts at address 0, but the registers keep whatever they had from
+//the last run, so nothing is assumed about them at the start.
+//
+//Every instruction but a taken jump goes on to the next address, so a graph
+//that followed every jump both ways could always reach the end of the
+//playfield. To tell which jumps can really be taken, the value of each
+//register is followed through the graph, as far as it is the same however
+//execution got there. A jump on a register known to be zero, or known not to
+//be, only goes one way, and a register jump only goes where the register
+//points if that is known. Otherwise it could go to any address, or off the
+//playfield.
+//
+//From the graph come:
+//  - the addresses execution can't reach, which are dead code.
+//  - the addresses execution can leave the playfield from, and whether it
+//    always does from there.
+//  - loops that, once entered, can never be left, since nothing in them can
+//    lead off the playfield.
+//
+//Everything reported is certain, except that an exit may turn out never to be
+//taken, because the graph includes every way execution might go.
+
+use std::fmt::Write;
+
+use Data::*;
+use Instruction::*;
+use {Instruction, Register, PLAYFIELD_SIZE, REGISTER_AMOUNT};
+
+/// Where execution can go after an address.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum Successor {
+    Address(usize),
+    /// Off the playfield, which stops execution.
+    Exit,
+}
+
+/// An address execution can leave the playfield from.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub struct Exit {
+    pub address: usize,
+    /// Whether execution always leaves from here, rather than only might.
+    pub always: bool,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Analysis {
+    /// Where execution can go from each address. Empty for the unreachable
+    /// ones.
+    pub successors: Vec<Vec<Successor>>,
+    pub reachable: [bool; PLAYFIELD_SIZE],
+    pub exits: Vec<Exit>,
+    /// Groups of addresses that execution can go round forever and never
+    /// leave, each in order.
+    pub loops: Vec<Vec<usize>>,
+}
+
+//what is known about a register at some address.
+#[derive(Clone, Copy, PartialEq)]
+enum Value {
+    Known(u8),
+    Unknown,
+}
+use self::Value::*;
+
+type State = [Value; REGISTER_AMOUNT];
+
+//where a jump goes when it is taken.
+#[derive(Clone, Copy)]
+enum Target {
+    Immediate(u8),
+    Register(Register),
+}
+
+pub fn analyse(instructions: &[Instruction; PLAYFIELD_SIZE]) -> Analysis {
+    //the registers at each address, or `None` where nothing has reached yet.
+    let mut states: Vec<Option<State>> = vec![None; PLAYFIELD_SIZE];
+    let mut successors = vec![Vec::new(); PLAYFIELD_SIZE];
+
+    states[0] = Some([Unknown; REGISTER_AMOUNT]);
+    let mut work = vec![0];
+
+    //each register can only go from known to unknown once at each address, so
+    //this stops.
+    while let Some(address) = work.pop() {
+        let state = match states[address] {
+            Some(state) => state,
+            None => continue,
+        };
+        let edges = edges(instructions[address], address, state);
+
+        successors[address].clear();
+        for (successor, state) in edges {
+            if !successors[address].contains(&successor) {
+                successors[address].push(successor);
+            }
+
+            if let Successor::Address(next) = successor {
+                let joined = match states[next] {
+                    Some(old) => join(old, state),
+                    None => state,
+                };
+                if states[next] != Some(joined) {
+                    states[next] = Some(joined);
+                    work.push(next);
+                }
+            }
+        }
+    }
+
+    let mut reachable = [false; PLAYFIELD_SIZE];
+    for (address, state) in states.iter().enumerate() {
+        reachable[address] = state.is_some();
+    }
+
+    let exits = (0..PLAYFIELD_SIZE)
+        .filter(|&address| successors[address].contains(&Successor::Exit))
+        .map(|address| {
+            Exit {
+                address,
+                always: successors[address].iter().all(|&successor| successor == Successor::Exit),
+            }
+        })
+        .collect();
+
+    let loops = loops(&successors, &reachable);
+
+    Analysis {
+        successors,
+        reachable,
+        exits,
+        loops,
+    }
+}
+
+impl Analysis {
+    pub fn unreachable(&self) -> Vec<usize> {
+        (0..PLAYFIELD_SIZE).filter(|&address| !self.reachable[address]).collect()
+    }
+
+    /// A plain text report of the unreachable addresses, the exits and the
+    /// loops that can't be left.
+    pub fn report(&self, instructions: &[Instruction; PLAYFIELD_SIZE]) -> String {
+        let mut text = String::new();
+
+        //writing to a `String` can't fail.
+        let unreachable = self.unreachable();
+        if unreachable.is_empty() {
+            let _ = writeln!(text, "unreachable none");
+        } else {
+            let _ = writeln!(text, "unreachable {}", ranges(&unreachable));
+        }
+
+        let _ = writeln!(text, "\nexits");
+        for exit in self.exits.iter() {
+            let _ = writeln!(text,
+                             "{:#04X}  {:<6}  {}",
+                             exit.address,
+                             if exit.always { "always" } else { "may" },
+                             instructions[exit.address]);
+        }
+
+        let _ = writeln!(text, "\nloops with no exit");
+        for l in self.loops.iter() {
+            let _ = writeln!(text, "{}", ranges(l));
+        }
+
+        text
+    }
+}
+
+//`addresses`, which are in order, with each run of them next to each other
+//written like `0x02-0x05`.
+fn ranges(addresses: &[usize]) -> String {
+    let mut runs: Vec<(usize, usize)> = Vec::new();
+    for &address in addresses {
+        match runs.last_mut() {
+            Some(&mut (_, ref mut end)) if *end + 1 == address => *end = address,
+            _ => runs.push((address, address)),
+        }
+    }
+
+    runs.iter()
+        .map(|&(start, end)| if start == end {
+            format!("{:#04X}", start)
+        } else {
+            format!("{:#04X}-{:#04X}", start, end)
+        })
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+//each way execution can go from `address`, along with what is known about the
+//registers on the way there.
+fn edges(instruction: Instruction, address: usize, state: State) -> Vec<(Successor, State)> {
+    let next = successor(address + 1);
+    let mut after = state;
+
+    match instruction {
+        NOP => {}
+        Load(Immeadiate(value), register) => after[register as usize] = Known(value),
+        Add(Immeadiate(value), register) => {
+            after[register as usize] = map(state[register as usize], |r| value.wrapping_add(r))
+        }
+        Sub(Immeadiate(value), register) => {
+            after[register as usize] = map(state[register as usize], |r| value.wrapping_sub(r))
+        }
+        JumpZero(Immeadiate(target), register) => {
+            return branch(state, register, true, Target::Immediate(target), next)
+        }
+        JumpNotZero(Immeadiate(target), register) => {
+            return branch(state, register, false, Target::Immediate(target), next)
+        }
+        JumpRZero(target, register) => {
+            return branch(state, register, true, Target::Register(target), next)
+        }
+        JumpRNotZero(target, register) => {
+            return branch(state, register, false, Target::Register(target), next)
+        }
+    }
+
+    vec![(next, after)]
+}
+
+//the edges of a jump on whether `condition` is zero, leaving out whichever
+//way can't happen.
+fn branch(state: State,
+          condition: Register,
+          if_zero: bool,
+          target: Target,
+          next: Successor)
+          -> Vec<(Successor, State)> {
+    let condition = condition as usize;
+    let (taken, not_taken) = match state[condition] {
+        Known(value) => ((value == 0) == if_zero, (value == 0) != if_zero),
+        Unknown => (true, true),
+    };
+
+    let mut edges = Vec::new();
+
+    if taken {
+        let mut state = state;
+        if if_zero {
+            state[condition] = Known(0);
+        }
+
+        let value = match target {
+            Target::Immediate(value) => Known(value),
+            Target::Register(register) => state[register as usize],
+        };
+        match value {
+            Known(value) => edges.push((successor(value as usize), state)),
+            Unknown => {
+                for address in 0..PLAYFIELD_SIZE {
+                    edges.push((Successor::Address(address), state));
+                }
+                edges.push((Successor::Exit, state));
+            }
+        }
+    }
+
+    if not_taken {
+        let mut state = state;
+        if !if_zero {
+            state[condition] = Known(0);
+        }
+
+        edges.push((next, state));
+    }
+
+    edges
+}
+
+fn successor(address: usize) -> Successor {
+    if address < PLAYFIELD_SIZE {
+        Successor::Address(address)
+    } else {
+        Successor::Exit
+    }
+}
+
+fn map<F: Fn(u8) -> u8>(value: Value, f: F) -> Value {
+    match value {
+        Known(value) => Known(f(value)),
+        Unknown => Unknown,
+    }
+}
+
+fn join(a: State, b: State) -> State {
+    let mut joined = a;
+    for (joined, &b) in joined.iter_mut().zip(b.iter()) {
+        if *joined != b {
+            *joined = Unknown;
+        }
+    }
+
+    joined
+}
+
+//the groups of reachable addresses that can reach each other but not the
+//exit.
+fn loops(successors: &[Vec<Successor>], reachable: &[bool; PLAYFIELD_SIZE]) -> Vec<Vec<usize>> {
+    //which addresses each one can get to in one step or more.
+    let reaches: Vec<Vec<bool>> = (0..PLAYFIELD_SIZE)
+        .map(|start| {
+            let mut seen = vec![false; PLAYFIELD_SIZE];
+            let mut work = vec![start];
+            while let Some(address) = work.pop() {
+                for &successor in successors[address].iter() {
+                    if let Successor::Address(next) = successor {
+                        if !seen[next] {
+                            seen[next] = true;
+                            work.push(next);
+                        }
+                    }
+                }
+            }
+
+            seen
+        })
+        .collect();
+
+    let can_exit: Vec<bool> = (0..PLAYFIELD_SIZE)
+        .map(|address| {
+            let exits_from = |address: usize| successors[address].contains(&Successor::Exit);
+            exits_from(address) ||
+            (0..PLAYFIELD_SIZE).any(|other| reaches[address][other] && exits_from(other))
+        })
+        .collect();
+
+    let mut loops: Vec<Vec<usize>> = Vec::new();
+    for address in 0..PLAYFIELD_SIZE {
+        if !reachable[address] || can_exit[address] || !reaches[address][address] {
+            continue;
+        }
+        if loops.iter().any(|l| l.contains(&address)) {
+            continue;
+        }
+
+        loops.push((address..PLAYFIELD_SIZE)
+            .filter(|&other| reaches[address][other] && reaches[other][address])
+            .collect());
+    }
+
+    loops
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    //a playfield starting with `lines`, with the rest left as `NOP`.
+    fn playfield(lines: &[&str]) -> [Instruction; PLAYFIELD_SIZE] {
+        let mut instructions = [NOP; PLAYFIELD_SIZE];
+        for (instruction, line) in instructions.iter_mut().zip(lines.iter()) {
+            *instruction = line.parse().unwrap();
+        }
+
+        instructions
+    }
+
+    #[test]
+    fn jumped_over_addresses_are_unreachable() {
+        let analysis = analyse(&playfield(&["load 0x01 A", "jnz 0x05 A"]));
+
+        assert_eq!(analysis.unreachable(), vec![2, 3, 4]);
+        assert_eq!(analysis.successors[1], vec![Successor::Address(5)]);
+        assert!(analysis.successors[3].is_empty());
+    }
+
+    #[test]
+    fn exits_are_always_only_when_nothing_else_can_happen() {
+        let analysis = analyse(&playfield(&["jz 0x20 A", "load 0x00 B", "jz 0x20 B"]));
+
+        assert_eq!(analysis.exits,
+                   vec![Exit {
+                            address: 0,
+                            always: false,
+                        },
+                        Exit {
+                            address: 2,
+                            always: true,
+                        }]);
+    }
+
+    #[test]
+    fn register_jumps_go_anywhere_unless_the_register_is_known() {
+        let analysis = analyse(&playfield(&["jrz C A"]));
+        assert_eq!(analysis.successors[0].len(), PLAYFIELD_SIZE + 1);
+        assert!(analysis.successors[0].contains(&Successor::Exit));
+        assert!(analysis.unreachable().is_empty());
+
+        let analysis = analyse(&playfield(&["load 0x04 C", "load 0x00 A", "jrz C A"]));
+        assert_eq!(analysis.successors[2], vec![Successor::Address(4)]);
+        assert_eq!(analysis.unreachable(), vec![3]);
+    }
+
+    #[test]
+    fn only_loops_that_cant_be_left_are_reported() {
+        let analysis = analyse(&playfield(&["load 0x00 A", "nop", "jz 0x01 A"]));
+        assert_eq!(analysis.loops, vec![vec![1, 2]]);
+        assert!(analysis.exits.is_empty());
+
+        //`A` might not be zero, so this loop can fall through to the end.
+        let analysis = analyse(&playfield(&["jnz 0x00 A"]));
+        assert!(analysis.loops.is_empty());
+        assert_eq!(analysis.exits,
+                   vec![Exit {
+                            address: PLAYFIELD_SIZE - 1,
+                            always: true,
+                        }]);
+    }
+}
@@ -0,0 +1,477 @@
+//Which keys do what is decided by a `Bindings`, rather than by matching on
+//`KeyCode`s directly, so players can change them in a file. The file has one
+//line per action, naming the keys that trigger it, with `Ctrl+` and `Shift+`
+//in front of a key for the modifiers it needs:
+//
+//    # comments start with a hash
+//    Run = F5 Ctrl+Enter
+//    Pause = Space
+//    Quit =
+//
+//Actions the file doesn't mention keep their default keys, except any the file
+//has given to something else, and an action with nothing after the `=` is
+//unbound. The editor's actions only apply while an address is open in it, so
+//they can share keys with the rest of the game's, like `EditorClose = Escape`
+//alongside `Quit = Escape`. `Display` writes every action out in this format, which is also how
+//bindings are stored in saved games and replays.
+//
+//The left mouse button is for pointing at things, so it can't be bound.
+
+use std::collections::HashMap;
+use std::fmt;
+use std::str::FromStr;
+
+use {Event, KeyCode};
+
+/// Everything that can be bound to a key.
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+pub enum Action {
+    /// Quit the game, or put the selected card back if there is one.
+    Quit,
+    /// Open the new game screen.
+    Reset,
+    /// Scroll the playfield, or move the selected card, by one address.
+    ScrollUp,
+    ScrollDown,
+    /// Scroll the playfield, or move the selected card, by 0x10 addresses.
+    ScrollUpFast,
+    ScrollDownFast,
+    Run,
+    /// Pause or resume.
+    Pause,
+    /// Execute the current instruction straight away, then pause.
+    Step,
+    /// Stop execution.
+    Break,
+    NextCard,
+    PreviousCard,
+    SelectCard1,
+    SelectCard2,
+    SelectCard3,
+    SelectCard4,
+    SelectCard5,
+    SelectCard6,
+    SelectCard7,
+    SelectCard8,
+    SelectCard9,
+    /// Place the selected card where the keyboard has moved it to.
+    Place,
+    /// Put the selected card back.
+    Deselect,
+    ToggleEditor,
+    /// Write out what the profiler has counted, see the `profile` module.
+    ExportProfile,
+    /// Show the playfield one layer further back, see the `history` module.
+    PeelLayer,
+    /// Show the playfield one layer less far back.
+    UnpeelLayer,
+    /// Throw away the top layer at the address the selected card or the mouse
+    /// is over, uncovering what it was written over.
+    Scrape,
+    /// Choose the field left of the chosen one in the editor: the opcode,
+    /// data or a register.
+    EditorPreviousField,
+    EditorNextField,
+    /// Change the chosen field in the editor by one.
+    EditorIncrease,
+    EditorDecrease,
+    /// Change the chosen field in the editor by 0x10, if it is data.
+    EditorIncreaseFast,
+    EditorDecreaseFast,
+    /// Replace the instructions selected in the editor with NOPs.
+    EditorClear,
+    EditorCopy,
+    /// Paste what was copied at the address open in the editor.
+    EditorPaste,
+    EditorClose,
+}
+
+/// Where an action applies. Only actions of the same mode can clash over a
+/// key.
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+pub enum Mode {
+    Game,
+    /// While an address is open in the editor, before the game's actions.
+    Editor,
+}
+
+pub const ALL_ACTIONS: [Action; 38] = [Action::Quit,
+                                       Action::Reset,
+                                       Action::ScrollUp,
+                                       Action::ScrollDown,
+                                       Action::ScrollUpFast,
+                                       Action::ScrollDownFast,
+                                       Action::Run,
+                                       Action::Pause,
+                                       Action::Step,
+                                       Action::Break,
+                                       Action::NextCard,
+                                       Action::PreviousCard,
+                                       Action::SelectCard1,
+                                       Action::SelectCard2,
+                                       Action::SelectCard3,
+                                       Action::SelectCard4,
+                                       Action::SelectCard5,
+                                       Action::SelectCard6,
+                                       Action::SelectCard7,
+                                       Action::SelectCard8,
+                                       Action::SelectCard9,
+                                       Action::Place,
+                                       Action::Deselect,
+                                       Action::ToggleEditor,
+                                       Action::ExportProfile,
+                                       Action::PeelLayer,
+                                       Action::UnpeelLayer,
+                                       Action::Scrape,
+                                       Action::EditorPreviousField,
+                                       Action::EditorNextField,
+                                       Action::EditorIncrease,
+                                       Action::EditorDecrease,
+                                       Action::EditorIncreaseFast,
+                                       Action::EditorDecreaseFast,
+                                       Action::EditorClear,
+                                       Action::EditorCopy,
+                                       Action::EditorPaste,
+                                       Action::EditorClose];
+
+impl Action {
+    /// Which card in the hand, counting from 0, a `SelectCardN` action selects.
+    pub fn card_index(self) -> Option<usize> {
+        match self {
+            Action::SelectCard1 => Some(0),
+            Action::SelectCard2 => Some(1),
+            Action::SelectCard3 => Some(2),
+            Action::SelectCard4 => Some(3),
+            Action::SelectCard5 => Some(4),
+            Action::SelectCard6 => Some(5),
+            Action::SelectCard7 => Some(6),
+            Action::SelectCard8 => Some(7),
+            Action::SelectCard9 => Some(8),
+            _ => None,
+        }
+    }
+
+    pub fn mode(self) -> Mode {
+        match self {
+            Action::EditorPreviousField |
+            Action::EditorNextField |
+            Action::EditorIncrease |
+            Action::EditorDecrease |
+            Action::EditorIncreaseFast |
+            Action::EditorDecreaseFast |
+            Action::EditorClear |
+            Action::EditorCopy |
+            Action::EditorPaste |
+            Action::EditorClose => Mode::Editor,
+            _ => Mode::Game,
+        }
+    }
+}
+
+impl fmt::Display for Action {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{:?}", self)
+    }
+}
+
+impl FromStr for Action {
+    type Err = String;
+
+    /// Parses the name of the variant, as `Display` prints it.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let s = s.trim();
+
+        ALL_ACTIONS.iter()
+            .find(|action| action.to_string() == s)
+            .cloned()
+            .ok_or_else(|| format!("\"{}\" is not an action", s))
+    }
+}
+
+/// A key along with the modifiers that have to be held with it. The modifiers
+/// have to match exactly, so `Shift+F5` doesn't also trigger `F5`.
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+pub struct Binding {
+    pub key: KeyCode,
+    pub ctrl: bool,
+    pub shift: bool,
+}
+
+impl Binding {
+    pub fn new(key: KeyCode) -> Self {
+        Binding {
+            key,
+            ctrl: false,
+            shift: false,
+        }
+    }
+
+    pub fn ctrl(key: KeyCode) -> Self {
+        Binding { ctrl: true, ..Binding::new(key) }
+    }
+
+    pub fn shift(key: KeyCode) -> Self {
+        Binding { shift: true, ..Binding::new(key) }
+    }
+}
+
+impl fmt::Display for Binding {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        if self.ctrl {
+            write!(f, "Ctrl+")?;
+        }
+        if self.shift {
+            write!(f, "Shift+")?;
+        }
+
+        write!(f, "{:?}", self.key)
+    }
+}
+
+impl FromStr for Binding {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut parts: Vec<&str> = s.trim().split('+').collect();
+        let key: KeyCode = parts.pop().unwrap_or("").parse()?;
+
+        if key == KeyCode::MouseLeft {
+            return Err("MouseLeft is for pointing, so it can't be bound".to_string());
+        }
+
+        let mut binding = Binding::new(key);
+        for modifier in parts {
+            match modifier.to_lowercase().as_ref() {
+                "ctrl" => binding.ctrl = true,
+                "shift" => binding.shift = true,
+                _ => return Err(format!("\"{}\" is not a modifier", modifier)),
+            }
+        }
+
+        Ok(binding)
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Bindings {
+    keys: Vec<(Binding, Action)>,
+}
+
+impl Default for Bindings {
+    fn default() -> Self {
+        use KeyCode::*;
+        use self::Action::*;
+
+        Bindings {
+            keys: vec![(Binding::new(Escape), Quit),
+                       (Binding::ctrl(R), Reset),
+                       (Binding::new(Up), ScrollUp),
+                       (Binding::new(Down), ScrollDown),
+                       (Binding::shift(Up), ScrollUpFast),
+                       (Binding::shift(Down), ScrollDownFast),
+                       (Binding::new(F5), Run),
+                       (Binding::new(Space), Action::Pause),
+                       (Binding::new(F10), Step),
+                       (Binding::shift(F5), Break),
+                       (Binding::new(Tab), NextCard),
+                       (Binding::shift(Tab), PreviousCard),
+                       (Binding::new(Row1), SelectCard1),
+                       (Binding::new(Num1), SelectCard1),
+                       (Binding::new(Row2), SelectCard2),
+                       (Binding::new(Num2), SelectCard2),
+                       (Binding::new(Row3), SelectCard3),
+                       (Binding::new(Num3), SelectCard3),
+                       (Binding::new(Row4), SelectCard4),
+                       (Binding::new(Num4), SelectCard4),
+                       (Binding::new(Row5), SelectCard5),
+                       (Binding::new(Num5), SelectCard5),
+                       (Binding::new(Row6), SelectCard6),
+                       (Binding::new(Num6), SelectCard6),
+                       (Binding::new(Row7), SelectCard7),
+                       (Binding::new(Num7), SelectCard7),
+                       (Binding::new(Row8), SelectCard8),
+                       (Binding::new(Num8), SelectCard8),
+                       (Binding::new(Row9), SelectCard9),
+                       (Binding::new(Num9), SelectCard9),
+                       (Binding::new(Enter), Place),
+                       (Binding::new(NumEnter), Place),
+                       (Binding::new(Backspace), Deselect),
+                       (Binding::new(MouseRight), Deselect),
+                       (Binding::ctrl(E), ToggleEditor),
+                       (Binding::ctrl(P), ExportProfile),
+                       (Binding::new(LeftBracket), PeelLayer),
+                       (Binding::new(RightBracket), UnpeelLayer),
+                       (Binding::new(Delete), Scrape),
+                       (Binding::new(Left), EditorPreviousField),
+                       (Binding::new(Right), EditorNextField),
+                       (Binding::new(Up), EditorIncrease),
+                       (Binding::new(Down), EditorDecrease),
+                       (Binding::shift(Up), EditorIncreaseFast),
+                       (Binding::shift(Down), EditorDecreaseFast),
+                       (Binding::new(Delete), EditorClear),
+                       (Binding::new(Backspace), EditorClear),
+                       (Binding::ctrl(C), EditorCopy),
+                       (Binding::ctrl(V), EditorPaste),
+                       (Binding::new(Enter), EditorClose),
+                       (Binding::new(Escape), EditorClose)],
+        }
+    }
+}
+
+impl Bindings {
+    pub fn action(&self, mode: Mode, binding: Binding) -> Option<Action> {
+        self.keys
+            .iter()
+            .find(|&&(bound, action)| bound == binding && action.mode() == mode)
+            .map(|&(_, action)| action)
+    }
+
+    /// The action of `mode` a key press triggers, if any. Other events never
+    /// trigger one.
+    pub fn action_for_event(&self, mode: Mode, event: &Event) -> Option<Action> {
+        match *event {
+            Event::KeyPressed { key, ctrl, shift } => {
+                self.action(mode,
+                            Binding {
+                                key,
+                                ctrl,
+                                shift,
+                            })
+            }
+            _ => None,
+        }
+    }
+
+    pub fn bindings_for(&self, action: Action) -> Vec<Binding> {
+        self.keys.iter().filter(|&&(_, bound)| bound == action).map(|&(binding, _)| binding).collect()
+    }
+
+    /// Reads bindings in the format described at the top of this module,
+    /// starting from the defaults. Lines and keys that can't be used are
+    /// skipped rather than failing the whole file, and each is described in
+    /// the returned problems, as is any key bound to more than one action and
+    /// any action left without a key.
+    pub fn parse(text: &str) -> (Bindings, Vec<String>) {
+        let mut problems = Vec::new();
+
+        //what the text binds, and the line each action and key was first seen
+        //on. Keys are claimed separately for each mode.
+        let mut actions: HashMap<Action, usize> = HashMap::new();
+        let mut claimed: HashMap<(Mode, Binding), (Action, usize)> = HashMap::new();
+        let mut keys = Vec::new();
+
+        for (line_index, line) in text.lines().enumerate() {
+            let line_number = line_index + 1;
+            let mut problem = |message: String| problems.push(format!("line {}: {}", line_number, message));
+
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+
+            let (action_text, keys_text) = match line.find('=') {
+                Some(index) => (&line[..index], &line[index + 1..]),
+                None => {
+                    problem("expected \"ACTION = KEYS\"".to_string());
+                    continue;
+                }
+            };
+
+            let action: Action = match action_text.parse() {
+                Ok(action) => action,
+                Err(error) => {
+                    problem(error);
+                    continue;
+                }
+            };
+
+            if let Some(&first_line) = actions.get(&action) {
+                problem(format!("{} was already bound on line {}", action, first_line));
+                continue;
+            }
+            actions.insert(action, line_number);
+
+            for key_text in keys_text.split_whitespace() {
+                let binding: Binding = match key_text.parse() {
+                    Ok(binding) => binding,
+                    Err(error) => {
+                        problem(error);
+                        continue;
+                    }
+                };
+
+                match claimed.get(&(action.mode(), binding)) {
+                    Some(&(other, _)) if other == action => {}
+                    Some(&(other, other_line)) => {
+                        problem(format!("{} is already bound to {} on line {}",
+                                        binding,
+                                        other,
+                                        other_line))
+                    }
+                    None => {
+                        claimed.insert((action.mode(), binding), (action, line_number));
+                        keys.push((binding, action));
+                    }
+                }
+            }
+        }
+
+        //the defaults fill in whatever the text didn't mention.
+        let defaults = Bindings::default().keys.into_iter().filter(|&(binding, action)| {
+            !actions.contains_key(&action) && !claimed.contains_key(&(action.mode(), binding))
+        });
+        let mut bindings = Bindings { keys: defaults.chain(keys).collect() };
+        bindings.keys.sort_by_key(|&(_, action)| ALL_ACTIONS.iter().position(|&a| a == action));
+
+        for &action in ALL_ACTIONS.iter() {
+            if bindings.bindings_for(action).is_empty() && !actions.contains_key(&action) {
+                problems.push(format!("{} no longer has a key, since its keys are bound to \
+                                       other actions",
+                                      action));
+            }
+        }
+
+        (bindings, problems)
+    }
+}
+
+impl fmt::Display for Bindings {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        for &action in ALL_ACTIONS.iter() {
+            write!(f, "{} =", action)?;
+            for binding in self.bindings_for(action) {
+                write!(f, " {}", binding)?;
+            }
+            writeln!(f)?;
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn the_defaults_have_no_problems() {
+        let (bindings, problems) = Bindings::parse(&Bindings::default().to_string());
+
+        assert_eq!(problems, Vec::<String>::new());
+        assert_eq!(bindings, Bindings::default());
+    }
+
+    #[test]
+    fn keys_only_clash_within_a_mode() {
+        let (bindings, problems) = Bindings::parse("Quit = Q\nEditorClose = Q\n");
+
+        assert_eq!(problems, Vec::<String>::new());
+        assert_eq!(bindings.action(Mode::Game, Binding::new(KeyCode::Q)), Some(Action::Quit));
+        assert_eq!(bindings.action(Mode::Editor, Binding::new(KeyCode::Q)),
+                   Some(Action::EditorClose));
+
+        let (_, problems) = Bindings::parse("EditorClose = Q\nEditorCopy = Q\n");
+
+        assert_eq!(problems.len(), 1);
+    }
+}
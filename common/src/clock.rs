@@ -0,0 +1,34 @@
+//The game is updated in fixed steps of `TICK`, however fast the machine is and
+//however long drawing takes, so everything that happens over time, like
+//instructions executing, happens at the same speed everywhere. The time the
+//game sees through `Platform::time` counts those steps rather than reading the
+//system clock, so it only moves between updates, and a replay, which plays one
+//recorded frame per update, sees exactly the times the recording did.
+//
+//Like the rest of `Platform`, the count is kept in a thread local of whichever
+//copy of this crate the function pointer came from, which is the host's.
+
+use std::cell::Cell;
+use std::time::Duration;
+
+/// How much time passes in one update.
+pub const TICK: Duration = Duration::from_nanos(1_000_000_000 / TICKS_PER_SECOND);
+pub const TICKS_PER_SECOND: u64 = 60;
+
+thread_local!(static TICKS: Cell<u64> = const { Cell::new(0) });
+
+/// How long the game has been running, counted in whole ticks. This is what
+/// backends give the game as `Platform::time`.
+pub fn time() -> Duration {
+    Duration::from_nanos(TICKS.with(|ticks| ticks.get()) * TICK.as_nanos() as u64)
+}
+
+/// Moves the time on by one tick. Call after each update.
+pub fn advance() {
+    TICKS.with(|ticks| ticks.set(ticks.get() + 1));
+}
+
+/// Starts counting from zero again.
+pub fn reset() {
+    TICKS.with(|ticks| ticks.set(0));
+}
@@ -0,0 +1,180 @@
+//Placing a card writes over whatever was on the playfield, but what was there
+//isn't lost. Every address keeps a stack of layers, from the playfield the
+//game started with at the bottom up to what is on the playfield now, and each
+//layer remembers where it came from: which card wrote it, which player placed
+//that card, on which turn and at what time. A turn is one card being placed,
+//so the first card placed is turn 1. The starting playfield wasn't placed by
+//anyone.
+//
+//Scraping an address throws its top layer away and uncovers the one under
+//it. The starting playfield can't be scraped away.
+
+use std::fmt;
+use std::rc::Rc;
+use std::time::Duration;
+
+use {Instruction, PLAYFIELD_SIZE};
+
+/// Which card something came from. Cards are numbered from 1 in the order
+/// they are dealt.
+pub type CardId = u32;
+
+/// Who placed cards, numbered from 1. There is only ever player 1 for now.
+pub type Player = u8;
+
+#[derive(Clone, Copy, PartialEq)]
+pub struct Layer {
+    pub instruction: Instruction,
+    /// `None` for the starting playfield.
+    pub placement: Option<Placement>,
+}
+
+/// Where a layer came from.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub struct Placement {
+    pub card: CardId,
+    pub player: Player,
+    pub turn: u32,
+    /// The `Platform::time` the card was placed at.
+    pub time: Duration,
+}
+
+impl fmt::Display for Placement {
+    /// Like `card 3, player 1, turn 2, 1:05`.
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let seconds = self.time.as_secs();
+
+        write!(f,
+               "card {}, player {}, turn {}, {}:{:02}",
+               self.card,
+               self.player,
+               self.turn,
+               seconds / 60,
+               seconds % 60)
+    }
+}
+
+#[derive(Clone, PartialEq)]
+pub struct History {
+    //the layers at each address, bottom first, so the last one is what is on
+    //the playfield. There is always at least one. Copies of the history share
+    //each address's layers until one of them changes it, so copying costs
+    //the same however many cards have been placed.
+    cells: Vec<Rc<Vec<Layer>>>,
+}
+
+impl History {
+    /// A history where `instructions` are the starting playfield.
+    pub fn new(instructions: &[Instruction; PLAYFIELD_SIZE]) -> Self {
+        History {
+            cells: instructions.iter()
+                .map(|&instruction| {
+                    Rc::new(vec![Layer {
+                                     instruction,
+                                     placement: None,
+                                 }])
+                })
+                .collect(),
+        }
+    }
+
+    /// The layers at `address`, bottom first.
+    pub fn layers(&self, address: usize) -> &[Layer] {
+        self.cells.get(address).map_or(&[], |layers| &layers[..])
+    }
+
+    /// The layer `depth` layers under the top one at `address`, or the bottom
+    /// one if there aren't that many.
+    pub fn peeled(&self, address: usize, depth: usize) -> Option<Layer> {
+        let layers = self.layers(address);
+        let depth = depth.min(layers.len().saturating_sub(1));
+
+        layers.iter().rev().nth(depth).cloned()
+    }
+
+    /// Where the top layer at `address` came from.
+    pub fn placement(&self, address: usize) -> Option<Placement> {
+        self.layers(address).last().and_then(|layer| layer.placement)
+    }
+
+    /// Every address `card` wrote to, whether or not it has been written over
+    /// since.
+    pub fn footprint(&self, card: CardId) -> Vec<usize> {
+        let wrote = |layer: &Layer| layer.placement.is_some_and(|placement| placement.card == card);
+
+        (0..self.cells.len())
+            .filter(|&address| self.cells[address].iter().any(&wrote))
+            .collect()
+    }
+
+    /// The most layers any address has under its top one.
+    pub fn depth(&self) -> usize {
+        self.cells.iter().map(|layers| layers.len() - 1).max().unwrap_or(0)
+    }
+
+    /// Puts `layer` on top of `address`.
+    pub fn write(&mut self, address: usize, layer: Layer) {
+        if let Some(layers) = self.cells.get_mut(address) {
+            Rc::make_mut(layers).push(layer);
+        }
+    }
+
+    /// Changes the instruction on top of `address` without adding a layer,
+    /// like the editor does.
+    pub fn overwrite(&mut self, address: usize, instruction: Instruction) {
+        if let Some(layer) = self.cells
+            .get_mut(address)
+            .and_then(|layers| Rc::make_mut(layers).last_mut()) {
+            layer.instruction = instruction;
+        }
+    }
+
+    /// Throws away the top layer at `address`, and returns the instruction
+    /// that uncovers. Returns `None` if there is only the starting playfield.
+    pub fn scrape(&mut self, address: usize) -> Option<Instruction> {
+        match self.cells.get_mut(address) {
+            Some(layers) if layers.len() > 1 => {
+                let layers = Rc::make_mut(layers);
+                layers.pop();
+                layers.last().map(|layer| layer.instruction)
+            }
+            _ => None,
+        }
+    }
+
+    /// Replaces every layer at `address`, like when loading a saved game.
+    /// `layers` is bottom first and mustn't be empty.
+    pub fn replace(&mut self, address: usize, layers: Vec<Layer>) {
+        if let Some(cell) = self.cells.get_mut(address) {
+            if !layers.is_empty() {
+                *cell = Rc::new(layers);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use Instruction::NOP;
+
+    #[test]
+    fn copies_keep_their_own_layers() {
+        let mut history = History::new(&[NOP; PLAYFIELD_SIZE]);
+        let layer = Layer {
+            instruction: "load 0x01 A".parse().unwrap(),
+            placement: None,
+        };
+        history.write(3, layer);
+
+        let copy = history.clone();
+        history.write(3, layer);
+        history.scrape(5);
+        history.overwrite(3, NOP);
+
+        assert_eq!(copy.layers(3).len(), 2);
+        assert!(copy.layers(3)[1] == layer);
+        assert_eq!(history.layers(3).len(), 3);
+        assert!(history.layers(3)[2].instruction == NOP);
+    }
+}
@@ -1,8 +1,24 @@
 extern crate rand;
 
 use std::fmt;
+use std::str::FromStr;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
 use rand::{Rand, Rng, SeedableRng, StdRng};
 
+pub mod abi;
+pub mod analysis;
+pub mod bindings;
+pub mod clock;
+pub mod history;
+pub mod profile;
+pub mod recording;
+pub mod replay;
+pub mod save;
+pub mod screen;
+pub mod storage;
+pub mod theme;
+
+#[derive(Clone, Copy)]
 pub struct Platform {
     pub print_xy: fn(i32, i32, &str),
     pub clear: fn(Option<Rect>),
@@ -11,23 +27,123 @@ pub struct Platform {
     pub clicks: fn() -> i32,
     pub key_pressed: fn(KeyCode) -> bool,
     pub set_colors: fn(Color, Color),
+    /// How long the game has been running, which only moves between updates.
+    /// See the `clock` module.
+    pub time: fn() -> Duration,
+    /// The blob stored under a name, or `None` if nothing has been. See the
+    /// `storage` module for what names can be.
+    pub read_blob: fn(&str) -> Result<Option<Vec<u8>>, String>,
+    /// Stores a blob under a name, replacing whatever was there.
+    pub write_blob: fn(&str, &[u8]) -> Result<(), String>,
+    /// Records a line of diagnostics somewhere the player won't see it while
+    /// playing, but can look at afterwards.
+    pub log: fn(&str),
 }
 
 pub const PLAYFIELD_SIZE: usize = 32;
 
+#[derive(Clone)]
 pub struct Game {
     pub instructions: [Instruction; PLAYFIELD_SIZE],
+    //everything that was ever written to each address, with the top layers
+    //matching `instructions`.
+    pub history: history::History,
+    //how many cards have been placed.
+    pub turn: u32,
+    //who is placing cards.
+    pub player: history::Player,
+    //how many layers back the playfield is shown, while peeling it back.
+    pub peeled_layers: usize,
     pub scroll_offset: i32,
     pub cards: Vec<Card>,
+    pub next_card_id: history::CardId,
     pub selected_card: Option<usize>,
-    pub playfield_right_edge: i32,
+    pub layout: Layout,
     pub ui_context: UIContext,
-    pub run_button_spec: ButtonSpec,
     pub paused: bool,
     pub executing_address: Option<i32>,
     pub instruction_countdown: u16,
     pub registers: [u8; REGISTER_AMOUNT],
+    //the addresses most recently executed, oldest first.
+    pub trace: Vec<usize>,
+    pub trace_list: ListState,
+    pub profile: profile::Profile,
     pub rng: StdRng,
+    pub editor: Editor,
+    pub seed: Seed,
+    //kept so the new game screen can offer it without looking at the clock.
+    pub daily_seed: Seed,
+    pub new_game_screen: Option<NewGameScreen>,
+    //where the selected card goes when placed with the keyboard. `None` while
+    //it follows the mouse instead.
+    pub placement_cursor: Option<usize>,
+    pub bindings: bindings::Bindings,
+    pub theme: theme::Theme,
+    //what was last drawn, so only what changes has to be drawn again.
+    pub screen: screen::Screen,
+}
+
+pub type Seed = u32;
+
+/// The seed used when nothing else asks for a particular one, like the
+/// headless runner does.
+pub const DEFAULT_SEED: Seed = 42;
+
+/// The seed everyone playing on the same (UTC) day gets, which reads as the
+/// date, like `20170412`.
+pub fn daily_seed(time: SystemTime) -> Seed {
+    let days = time.duration_since(UNIX_EPOCH).map(|d| d.as_secs() / 86400).unwrap_or(0) as i64;
+
+    //from Howard Hinnant's `civil_from_days`, converting days since
+    //1970-01-01 to a year, month and day.
+    let z = days + 719468;
+    let era = z.div_euclid(146097);
+    let day_of_era = z - era * 146097;
+    let year_of_era = (day_of_era - day_of_era / 1460 + day_of_era / 36524 -
+                       day_of_era / 146096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let shifted_month = (5 * day_of_year + 2) / 153;
+    let day = day_of_year - (153 * shifted_month + 2) / 5 + 1;
+    let month = if shifted_month < 10 {
+        shifted_month + 3
+    } else {
+        shifted_month - 9
+    };
+    let year = year_of_era + era * 400 + if month <= 2 { 1 } else { 0 };
+
+    (year * 10000 + month * 100 + day) as Seed
+}
+
+#[derive(Clone, Default)]
+pub struct NewGameScreen {
+    pub seed_text: String,
+    //read from storage the first frame the screen is open, newest first.
+    pub recent_seeds: Option<Vec<Seed>>,
+    pub recent_list: ListState,
+}
+
+#[derive(Clone, Default)]
+pub struct Editor {
+    pub enabled: bool,
+    pub address: Option<usize>,
+    pub field: usize,
+    //the other end of the selection, `address` being the first end.
+    pub selection_end: Option<usize>,
+    pub clipboard: Vec<Instruction>,
+}
+
+impl Editor {
+    /// The inclusive range of selected addresses, if any.
+    pub fn selection(&self) -> Option<(usize, usize)> {
+        self.address.map(|address| {
+            let end = self.selection_end.unwrap_or(address);
+            if end < address {
+                (end, address)
+            } else {
+                (address, end)
+            }
+        })
+    }
 }
 
 pub const REGISTER_AMOUNT: usize = 8;
@@ -66,6 +182,24 @@ impl fmt::Display for Register {
     }
 }
 
+impl FromStr for Register {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.trim() {
+            "A" | "a" => Ok(A),
+            "B" | "b" => Ok(B),
+            "C" | "c" => Ok(C),
+            "D" | "d" => Ok(D),
+            "E" | "e" => Ok(E),
+            "F" | "f" => Ok(F),
+            "G" | "g" => Ok(G),
+            "H" | "h" => Ok(H),
+            other => Err(format!("\"{}\" is not a register", other)),
+        }
+    }
+}
+
 pub const REGISTER_VARIATION_COUNT: u8 = 4;
 
 impl Rand for Register {
@@ -83,10 +217,13 @@ impl Rand for Register {
     }
 }
 
+#[derive(Clone)]
 pub struct UIContext {
     pub hot: UiId,
     pub active: UiId, // pub interacting_with: UiId,
     pub next_hot: UiId,
+    //the widget keys are typed into, or 0 for none.
+    pub focused: UiId,
 }
 
 impl UIContext {
@@ -112,6 +249,13 @@ impl UIContext {
 
 pub type UiId = i32;
 
+/// What a scrollable list widget needs to remember between frames.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct ListState {
+    /// The index of the row shown at the top.
+    pub scroll: usize,
+}
+
 pub struct ButtonSpec {
     pub x: i32,
     pub y: i32,
@@ -120,14 +264,33 @@ pub struct ButtonSpec {
     pub text: String,
 }
 
+/// Where each part of the screen goes, worked out from the window size by the
+/// `game` crate whenever that changes.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Layout {
+    pub size: Size,
+    /// Where the arrows from jumps to their targets go, left of the playfield.
+    pub gutter: Rect,
+    pub playfield: Rect,
+    /// How often each address has been executed, right of the playfield.
+    pub heatmap: Rect,
+    pub trace: Rect,
+    pub registers: Rect,
+    pub controls: Rect,
+    pub hand: Rect,
+}
+
+#[derive(Clone)]
 pub struct Card {
+    pub id: history::CardId,
     pub location: Point,
     pub instructions: Vec<Instruction>,
 }
 
 impl Card {
-    pub fn new(x: i32, y: i32, instructions: Vec<Instruction>) -> Self {
+    pub fn new(id: history::CardId, x: i32, y: i32, instructions: Vec<Instruction>) -> Self {
         Card {
+            id,
             location: Point::new(x, y),
             instructions: instructions,
         }
@@ -149,24 +312,186 @@ use Instruction::*;
 
 impl fmt::Display for Instruction {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        for (_, text) in self.tokens() {
+            f.write_str(&text)?;
+        }
+
+        Ok(())
+    }
+}
+
+impl FromStr for Instruction {
+    type Err = String;
+
+    /// Parses the format produced by `Display`, ignoring case in the mnemonic
+    /// and any extra whitespace.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let parts: Vec<&str> = s.split_whitespace().collect();
+
+        let mnemonic = match parts.first() {
+            Some(mnemonic) => mnemonic.to_lowercase(),
+            None => return Err("missing instruction".to_string()),
+        };
+
+        let expected_parts = if mnemonic == "nop" { 1 } else { 3 };
+        if parts.len() != expected_parts {
+            return Err(format!("\"{}\" should have {} operand(s)",
+                               s.trim(),
+                               expected_parts - 1));
+        }
+
+        match mnemonic.as_ref() {
+            "nop" => Ok(NOP),
+            "load" => Ok(Load(parts[1].parse()?, parts[2].parse()?)),
+            "add" => Ok(Add(parts[1].parse()?, parts[2].parse()?)),
+            "sub" => Ok(Sub(parts[1].parse()?, parts[2].parse()?)),
+            "jz" => Ok(JumpZero(parts[1].parse()?, parts[2].parse()?)),
+            "jnz" => Ok(JumpNotZero(parts[1].parse()?, parts[2].parse()?)),
+            "jrz" => Ok(JumpRZero(parts[1].parse()?, parts[2].parse()?)),
+            "jrnz" => Ok(JumpRNotZero(parts[1].parse()?, parts[2].parse()?)),
+            other => Err(format!("\"{}\" is not an instruction", other)),
+        }
+    }
+}
+
+pub const INSTRUCTION_VARIATION_COUNT: u8 = 8;
+
+impl Instruction {
+    /// The index of this instruction's variant, matching the order used by `Rand`.
+    pub fn opcode(&self) -> u8 {
         match *self {
-            Instruction::NOP => write!(f, "{}", "NOP"),
-            Instruction::Load(data, register) => write!(f, "load {} {}", data, register),
-            Instruction::Add(data, register) => write!(f, "add  {} {}", data, register),
-            Instruction::Sub(data, register) => write!(f, "sub  {} {}", data, register),
-            Instruction::JumpZero(data, register) => write!(f, "JZ  {} {}", data, register),
-            Instruction::JumpNotZero(data, register) => write!(f, "JNZ  {} {}", data, register),
-            Instruction::JumpRZero(register1, register2) => {
-                write!(f, "JRZ {} {}", register1, register2)
+            NOP => 0,
+            Load(_, _) => 1,
+            Add(_, _) => 2,
+            Sub(_, _) => 3,
+            JumpZero(_, _) => 4,
+            JumpNotZero(_, _) => 5,
+            JumpRZero(_, _) => 6,
+            JumpRNotZero(_, _) => 7,
+        }
+    }
+
+    /// Splits the instruction into its opcode, data and two register slots.
+    /// Slots the instruction doesn't use are filled with defaults.
+    pub fn decompose(&self) -> (u8, Data, Register, Register) {
+        let default_data = Immeadiate(0);
+        match *self {
+            NOP => (0, default_data, A, A),
+            Load(data, register) |
+            Add(data, register) |
+            Sub(data, register) |
+            JumpZero(data, register) |
+            JumpNotZero(data, register) => (self.opcode(), data, register, A),
+            JumpRZero(register1, register2) |
+            JumpRNotZero(register1, register2) => {
+                (self.opcode(), default_data, register1, register2)
             }
-            Instruction::JumpRNotZero(register1, register2) => {
-                write!(f, "JRNZ {} {}", register1, register2)
+        }
+    }
+
+    /// The inverse of `decompose`. Opcodes are taken modulo `INSTRUCTION_VARIATION_COUNT`.
+    pub fn compose(opcode: u8,
+                   data: Data,
+                   register1: Register,
+                   register2: Register)
+                   -> Instruction {
+        match opcode % INSTRUCTION_VARIATION_COUNT {
+            1 => Load(data, register1),
+            2 => Add(data, register1),
+            3 => Sub(data, register1),
+            4 => JumpZero(data, register1),
+            5 => JumpNotZero(data, register1),
+            6 => JumpRZero(register1, register2),
+            7 => JumpRNotZero(register1, register2),
+            _ => NOP,
+        }
+    }
+
+    pub fn family(&self) -> OpcodeFamily {
+        match *self {
+            NOP => OpcodeFamily::Nop,
+            Load(_, _) => OpcodeFamily::Load,
+            Add(_, _) | Sub(_, _) => OpcodeFamily::Arithmetic,
+            JumpZero(_, _) | JumpNotZero(_, _) | JumpRZero(_, _) | JumpRNotZero(_, _) => {
+                OpcodeFamily::Jump
             }
         }
     }
-}
 
-pub const INSTRUCTION_VARIATION_COUNT: u8 = 8;
+    /// The printed form of the instruction split into tokens, which put back
+    /// together are what `Display` writes.
+    pub fn tokens(&self) -> Vec<(Token, String)> {
+        let mnemonic = (Token::Opcode(self.family()), self.mnemonic().to_string());
+
+        let (gap, operands) = match *self {
+            NOP => return vec![mnemonic],
+            Load(data, register) => {
+                (" ",
+                 [(Token::Immediate, data.to_string()), (Token::Register, register.to_string())])
+            }
+            Add(data, register) |
+            Sub(data, register) => {
+                ("  ",
+                 [(Token::Immediate, data.to_string()), (Token::Register, register.to_string())])
+            }
+            JumpZero(data, register) |
+            JumpNotZero(data, register) => {
+                ("  ",
+                 [(Token::JumpTarget, data.to_string()), (Token::Register, register.to_string())])
+            }
+            JumpRZero(register1, register2) |
+            JumpRNotZero(register1, register2) => {
+                (" ",
+                 [(Token::Register, register1.to_string()),
+                  (Token::Register, register2.to_string())])
+            }
+        };
+        let [first, second] = operands;
+
+        vec![mnemonic,
+             (Token::Space, gap.to_string()),
+             first,
+             (Token::Space, " ".to_string()),
+             second]
+    }
+
+    pub fn mnemonic(&self) -> &'static str {
+        match *self {
+            NOP => "NOP",
+            Load(_, _) => "load",
+            Add(_, _) => "add",
+            Sub(_, _) => "sub",
+            JumpZero(_, _) => "JZ",
+            JumpNotZero(_, _) => "JNZ",
+            JumpRZero(_, _) => "JRZ",
+            JumpRNotZero(_, _) => "JRNZ",
+        }
+    }
+
+    /// The printed form of each editable part of the instruction, mnemonic first.
+    pub fn fields(&self) -> Vec<String> {
+        let mut result = vec![self.mnemonic().to_string()];
+
+        match *self {
+            NOP => {}
+            Load(data, register) |
+            Add(data, register) |
+            Sub(data, register) |
+            JumpZero(data, register) |
+            JumpNotZero(data, register) => {
+                result.push(format!("{}", data));
+                result.push(format!("{}", register));
+            }
+            JumpRZero(register1, register2) |
+            JumpRNotZero(register1, register2) => {
+                result.push(format!("{}", register1));
+                result.push(format!("{}", register2));
+            }
+        }
+
+        result
+    }
+}
 
 impl Rand for Instruction {
     fn rand<R: Rng>(rng: &mut R) -> Self {
@@ -183,6 +508,29 @@ impl Rand for Instruction {
     }
 }
 
+/// The kinds of instruction, which are told apart by colour when drawn.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum OpcodeFamily {
+    Nop,
+    Load,
+    /// `add` and `sub`.
+    Arithmetic,
+    /// Every kind of jump.
+    Jump,
+}
+
+/// What a piece of an instruction's printed form is, see `Instruction::tokens`.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum Token {
+    Opcode(OpcodeFamily),
+    Immediate,
+    /// An immediate value that is an address to jump to.
+    JumpTarget,
+    Register,
+    /// The spaces between the other tokens.
+    Space,
+}
+
 #[derive(Debug, Clone, Copy, PartialEq)]
 pub enum Data {
     Immeadiate(u8),
@@ -197,6 +545,22 @@ impl fmt::Display for Data {
     }
 }
 
+impl FromStr for Data {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let s = s.trim();
+
+        let parsed = if s.starts_with("0x") || s.starts_with("0X") {
+            u8::from_str_radix(&s[2..], 16)
+        } else {
+            s.parse::<u8>()
+        };
+
+        parsed.map(Immeadiate).map_err(|_| format!("\"{}\" is not a byte value", s))
+    }
+}
+
 pub const DATA_VARIATION_COUNT: u8 = 1;
 
 impl Rand for Data {
@@ -224,6 +588,43 @@ pub fn get_instructions() -> [Instruction; PLAYFIELD_SIZE] {
     result
 }
 
+/// Parses a playfield written one instruction per line, as `draw_instructions`
+/// shows it. A line may start with an address followed by `│` or `:`, which
+/// moves the following lines to that address. Blank lines and anything after
+/// a `#` are ignored, and unmentioned addresses hold `NOP`.
+pub fn parse_playfield(text: &str) -> Result<[Instruction; PLAYFIELD_SIZE], String> {
+    let mut result = [NOP; PLAYFIELD_SIZE];
+
+    let mut address = 0;
+    for (line_index, full_line) in text.lines().enumerate() {
+        let line = full_line.split('#').next().unwrap_or("").trim();
+        if line.is_empty() {
+            continue;
+        }
+
+        let error = |message: String| format!("line {}: {}", line_index + 1, message);
+
+        let instruction_text = match line.find(['│', ':']) {
+            Some(index) => {
+                let Immeadiate(value) = line[..index].parse::<Data>().map_err(&error)?;
+                address = value as usize;
+
+                &line[index + line[index..].chars().next().map_or(1, |c| c.len_utf8())..]
+            }
+            None => line,
+        };
+
+        if address >= PLAYFIELD_SIZE {
+            return Err(error(format!("address {:#04X} is off the playfield", address)));
+        }
+
+        result[address] = instruction_text.parse().map_err(&error)?;
+        address += 1;
+    }
+
+    Ok(result)
+}
+
 impl Point {
     /// Creates a new point on the specified non-negative coordinates
     pub fn new_safe(mut x: i32, mut y: i32) -> Point {
@@ -237,6 +638,16 @@ impl Point {
         Point::new_safe(self.x + x, self.y + y)
     }
 }
+
+impl Register {
+    /// Cycles through all `REGISTER_AMOUNT` registers, wrapping at either end.
+    pub fn offset(&self, delta: i32) -> Register {
+        let amount = REGISTER_AMOUNT as i32;
+        let index = ((*self as i32 + delta) % amount + amount) % amount;
+
+        to_register(index).unwrap_or(A)
+    }
+}
 //if I import BearLibTerminal.rs into `game` or a crate `game` depends on,
 //like this one for example, then the ffi to the C version of
 //BearLibTerminal causes an error. I just want the geometry datatypes and
@@ -393,9 +804,22 @@ impl Rect {
 
 //input module
 
-/// All pressable keys.
-#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
-pub enum KeyCode {
+//declares `KeyCode` along with `ALL_KEY_CODES`, so a key can't be left out of
+//the list.
+macro_rules! key_codes {
+    ($($(#[$doc:meta])* $key:ident,)*) => {
+        /// All pressable keys.
+        #[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+        pub enum KeyCode {
+            $($(#[$doc])* $key,)*
+        }
+
+        /// Every `KeyCode`, in declaration order.
+        pub const ALL_KEY_CODES: &[KeyCode] = &[$(KeyCode::$key),*];
+    };
+}
+
+key_codes! {
     A,
     B,
     C,
@@ -538,6 +962,20 @@ pub enum KeyCode {
     MouseFifth,
 }
 
+impl FromStr for KeyCode {
+    type Err = String;
+
+    /// Parses the name of the variant, as `Debug` prints it.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let s = s.trim();
+
+        ALL_KEY_CODES.iter()
+            .find(|key| format!("{:?}", key) == s)
+            .cloned()
+            .ok_or_else(|| format!("\"{}\" is not a key", s))
+    }
+}
+
 /// A single input event.
 #[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
 pub enum Event {
@@ -600,9 +1038,74 @@ pub enum Event {
     ControlReleased,
 }
 
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
 pub struct Color {
     pub red: u8,
     pub green: u8,
     pub blue: u8,
     pub alpha: u8,
 }
+
+impl Color {
+    pub const fn rgb(red: u8, green: u8, blue: u8) -> Self {
+        Color {
+            red,
+            green,
+            blue,
+            alpha: 255,
+        }
+    }
+
+    /// `amount` of the way from `self` to `other`, where 0 is `self` and 1 is
+    /// `other`.
+    pub fn mix(self, other: Color, amount: f32) -> Color {
+        let amount = amount.clamp(0.0, 1.0);
+        let channel = |from: u8, to: u8| {
+            (from as f32 + (to as f32 - from as f32) * amount).round() as u8
+        };
+
+        Color {
+            red: channel(self.red, other.red),
+            green: channel(self.green, other.green),
+            blue: channel(self.blue, other.blue),
+            alpha: channel(self.alpha, other.alpha),
+        }
+    }
+}
+
+impl fmt::Display for Color {
+    /// Writes `#RRGGBB`, with the alpha on the end if it isn't opaque.
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "#{:02X}{:02X}{:02X}", self.red, self.green, self.blue)?;
+        if self.alpha != 255 {
+            write!(f, "{:02X}", self.alpha)?;
+        }
+
+        Ok(())
+    }
+}
+
+impl FromStr for Color {
+    type Err = String;
+
+    /// Parses `#RRGGBB` or `#RRGGBBAA`.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let s = s.trim();
+        let error = || format!("\"{}\" is not a colour, expected #RRGGBB", s);
+
+        let digits = match s.strip_prefix('#') {
+            Some(digits) if (digits.len() == 6 || digits.len() == 8) && digits.is_ascii() => digits,
+            _ => return Err(error()),
+        };
+        let component = |index: usize| {
+            u8::from_str_radix(&digits[index * 2..index * 2 + 2], 16).map_err(|_| error())
+        };
+
+        Ok(Color {
+            red: component(0)?,
+            green: component(1)?,
+            blue: component(2)?,
+            alpha: if digits.len() == 8 { component(3)? } else { 255 },
+        })
+    }
+}
@@ -0,0 +1,145 @@
+//While the playfield runs, `Profile` counts how many times each address is
+//executed, and how long is spent going round each loop, to show which cards
+//actually mattered in a run. It starts again whenever the playfield is run
+//from the top.
+//
+//A loop is a jump back to an earlier address, or the same one, that was
+//taken. Going round it once, an iteration, takes every step from arriving at
+//the address jumped back to, up to and including the jump. Time is counted in
+//steps, which is the number of instructions executed, since each one takes
+//the same number of updates.
+
+use std::fmt::Write;
+
+use {Instruction, PLAYFIELD_SIZE};
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Profile {
+    /// How many times each address was executed.
+    pub counts: [u64; PLAYFIELD_SIZE],
+    pub loops: Vec<Loop>,
+    /// How many instructions have been executed in total.
+    pub steps: u64,
+    //the step each address was last executed on.
+    last_executed: [Option<u64>; PLAYFIELD_SIZE],
+}
+
+/// A jump from `end` back to `start`, and what going round it has cost.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub struct Loop {
+    pub start: usize,
+    pub end: usize,
+    pub iterations: u64,
+    pub steps: u64,
+}
+
+impl Default for Profile {
+    fn default() -> Self {
+        Profile {
+            counts: [0; PLAYFIELD_SIZE],
+            loops: Vec::new(),
+            steps: 0,
+            last_executed: [None; PLAYFIELD_SIZE],
+        }
+    }
+}
+
+impl Profile {
+    /// Counts executing the instruction at `address`, which went on to `next`.
+    pub fn record(&mut self, address: usize, next: i32) {
+        if address >= PLAYFIELD_SIZE {
+            return;
+        }
+
+        self.steps += 1;
+        self.counts[address] += 1;
+        self.last_executed[address] = Some(self.steps);
+
+        if next < 0 || next as usize > address {
+            return;
+        }
+
+        let start = next as usize;
+        //with nothing to go on, like after jumping into the middle of a loop,
+        //the jump back is all there is to count.
+        let iteration_steps = self.steps - self.last_executed[start].unwrap_or(self.steps) + 1;
+
+        match self.loops.iter().position(|l| l.start == start && l.end == address) {
+            Some(index) => {
+                self.loops[index].iterations += 1;
+                self.loops[index].steps += iteration_steps;
+            }
+            None => {
+                self.loops.push(Loop {
+                    start,
+                    end: address,
+                    iterations: 1,
+                    steps: iteration_steps,
+                })
+            }
+        }
+    }
+
+    /// The most times any address has been executed.
+    pub fn hottest(&self) -> u64 {
+        self.counts.iter().cloned().max().unwrap_or(0)
+    }
+
+    /// How hot `address` is, from 0 for never executed to 1 for the hottest.
+    /// Counts are compared on a log scale, so an address run ten times still
+    /// shows up next to a loop run thousands of times.
+    pub fn heat(&self, address: usize) -> f32 {
+        let count = self.counts.get(address).cloned().unwrap_or(0);
+        let hottest = self.hottest();
+
+        if count == 0 || hottest == 0 {
+            0.0
+        } else {
+            ((count as f32).ln_1p() / (hottest as f32).ln_1p()).clamp(0.0, 1.0)
+        }
+    }
+
+    /// The loops, the one that took the most steps first.
+    pub fn loops_by_steps(&self) -> Vec<Loop> {
+        let mut loops = self.loops.clone();
+        loops.sort_by(|a, b| b.steps.cmp(&a.steps).then(a.start.cmp(&b.start)));
+
+        loops
+    }
+
+    /// A plain text report, with every address executed along with what is
+    /// there now, and then every loop.
+    pub fn report(&self, instructions: &[Instruction; PLAYFIELD_SIZE]) -> String {
+        let mut text = String::new();
+
+        //writing to a `String` can't fail.
+        let _ = writeln!(text, "steps {}", self.steps);
+
+        let _ = writeln!(text, "\naddress  {:>10}  {:>5}  instruction", "count", "share");
+        for (address, &count) in self.counts.iter().enumerate().filter(|&(_, &count)| count > 0) {
+            let _ = writeln!(text,
+                             "{:<7}  {:>10}  {:>5}  {}",
+                             format!("{:#04X}", address),
+                             count,
+                             self.share(count),
+                             instructions[address]);
+        }
+
+        let _ = writeln!(text, "\nloop       {:>10}  {:>10}  {:>5}", "iterations", "steps", "share");
+        for l in self.loops_by_steps() {
+            let _ = writeln!(text,
+                             "{:<9}  {:>10}  {:>10}  {:>5}",
+                             format!("{:#04X}-{:#04X}", l.start, l.end),
+                             l.iterations,
+                             l.steps,
+                             self.share(l.steps));
+        }
+
+        text
+    }
+
+    //`steps` as a percentage of all of them.
+    fn share(&self, steps: u64) -> String {
+        format!("{:.0}%", 100.0 * steps as f64 / self.steps.max(1) as f64)
+    }
+}
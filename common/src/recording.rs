@@ -0,0 +1,323 @@
+//A `Platform` that draws into an in-memory grid of characters instead of a
+//window, with input that comes from scripted `Event` lists. This makes it
+//possible to check exactly what ends up on screen after a given series of
+//inputs, for example:
+//
+//    recording::reset(Size::new(80, 30));
+//    let platform = recording::platform();
+//    let mut frames = recording::click(card_x, card_y);
+//    frames.extend(recording::click(address_x, address_y));
+//    frames.extend(recording::wait(3));
+//    recording::play(update_and_render, &platform, &mut game, frames);
+//    assert_eq!(recording::screen(), expected);
+//
+//`Platform` is a table of plain function pointers, so the recorded state is
+//kept in a thread local, and each thread has its own screen. Blobs the game
+//stores and lines it logs are kept there too, starting out empty, and can be
+//looked at with `blob` and `log_lines`.
+
+use std::cell::RefCell;
+use std::collections::HashMap;
+
+use {clock, storage, Color, Event, Game, KeyCode, Platform, Point, Rect, Size};
+
+pub use abi::UpdateAndRender;
+
+pub type Rgba = (u8, u8, u8, u8);
+
+const DEFAULT_FG: Rgba = (255, 255, 255, 255);
+const DEFAULT_BG: Rgba = (0, 0, 0, 255);
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Cell {
+    pub character: char,
+    pub fg: Rgba,
+    pub bg: Rgba,
+}
+
+const BLANK: Cell = Cell {
+    character: ' ',
+    fg: DEFAULT_FG,
+    bg: DEFAULT_BG,
+};
+
+struct State {
+    size: Size,
+    cells: Vec<Cell>,
+    //what was drawn by the last frame that didn't quit, like a window would show.
+    presented: Vec<Cell>,
+    fg: Rgba,
+    bg: Rgba,
+    mouse_position: Point,
+    keys_down: Vec<KeyCode>,
+    print_calls: usize,
+    blobs: HashMap<String, Vec<u8>>,
+    log_lines: Vec<String>,
+}
+
+impl State {
+    fn new(size: Size) -> Self {
+        State {
+            size,
+            cells: vec![BLANK; (size.width * size.height) as usize],
+            presented: vec![BLANK; (size.width * size.height) as usize],
+            fg: DEFAULT_FG,
+            bg: DEFAULT_BG,
+            mouse_position: Point::new(0, 0),
+            keys_down: Vec::new(),
+            print_calls: 0,
+            blobs: HashMap::new(),
+            log_lines: Vec::new(),
+        }
+    }
+
+    fn index(&self, x: i32, y: i32) -> Option<usize> {
+        if x < 0 || y < 0 || x >= self.size.width || y >= self.size.height {
+            None
+        } else {
+            Some((y * self.size.width + x) as usize)
+        }
+    }
+}
+
+thread_local! {
+    static STATE: RefCell<State> = RefCell::new(State::new(Size::new(80, 30)));
+}
+
+pub fn platform() -> Platform {
+    Platform {
+        print_xy,
+        clear,
+        size,
+        mouse_position,
+        clicks,
+        key_pressed,
+        set_colors,
+        time: clock::time,
+        read_blob,
+        write_blob,
+        log,
+    }
+}
+
+/// Blanks the screen, releases all keys, moves the mouse to the top left,
+/// empties storage and the log and sets the clock back to zero.
+pub fn reset(size: Size) {
+    STATE.with(|state| *state.borrow_mut() = State::new(size));
+    clock::reset();
+}
+
+/// Updates the recorded input state from `events`, the way a window would
+/// before handing them to the game, then runs one frame, which moves the clock
+/// on by a tick. Like a window, the screen keeps whatever was drawn on it
+/// until it is drawn over. Returns true if the game asked to quit.
+pub fn run_frame(update_and_render: UpdateAndRender,
+                 platform: &Platform,
+                 game: &mut Game,
+                 mut events: Vec<Event>)
+                 -> bool {
+    STATE.with(|state| {
+        let mut state = state.borrow_mut();
+
+        for event in events.iter() {
+            match *event {
+                Event::MouseMove { x, y } => state.mouse_position = Point::new_safe(x, y),
+                Event::KeyPressed { key, ctrl: _, shift: _ } if !state.keys_down.contains(&key) => {
+                    state.keys_down.push(key);
+                }
+                Event::KeyReleased { key, ctrl: _, shift: _ } => {
+                    state.keys_down.retain(|&down| down != key);
+                }
+                Event::Resize { width, height } => {
+                    let resized = State::new(Size::new(width, height));
+
+                    state.size = resized.size;
+                    state.cells = resized.cells;
+                    state.presented = resized.presented;
+                }
+                _ => {}
+            }
+        }
+    });
+
+    let quit = update_and_render(platform, game, &mut events);
+    clock::advance();
+
+    if !quit {
+        STATE.with(|state| {
+            let mut state = state.borrow_mut();
+            state.presented = state.cells.clone();
+        });
+    }
+
+    quit
+}
+
+/// Runs each list of events as its own frame, stopping early if the game
+/// asks to quit. Returns true if it did.
+pub fn play(update_and_render: UpdateAndRender,
+            platform: &Platform,
+            game: &mut Game,
+            frames: Vec<Vec<Event>>)
+            -> bool {
+    for events in frames {
+        if run_frame(update_and_render, platform, game, events) {
+            return true;
+        }
+    }
+
+    false
+}
+
+pub fn key(key: KeyCode) -> Event {
+    Event::KeyPressed {
+        key,
+        ctrl: false,
+        shift: false,
+    }
+}
+
+pub fn ctrl_key(key: KeyCode) -> Event {
+    Event::KeyPressed {
+        key,
+        ctrl: true,
+        shift: false,
+    }
+}
+
+/// The frames for moving the mouse to the given position, then pressing the
+/// left button and releasing it. Each happens on its own frame, since buttons
+/// need to be hovered over for a frame before they can be pressed.
+pub fn click(x: i32, y: i32) -> Vec<Vec<Event>> {
+    vec![vec![Event::MouseMove { x, y }],
+         vec![key(KeyCode::MouseLeft)],
+         vec![Event::KeyReleased {
+                  key: KeyCode::MouseLeft,
+                  ctrl: false,
+                  shift: false,
+              }]]
+}
+
+/// `frames` frames without any input.
+pub fn wait(frames: usize) -> Vec<Vec<Event>> {
+    vec![Vec::new(); frames]
+}
+
+/// The whole screen as text, one line per row, with trailing spaces removed.
+/// If the last frame quit, this is the screen from the frame before it.
+pub fn screen() -> String {
+    STATE.with(|state| {
+        let state = state.borrow();
+
+        state.presented
+            .chunks(state.size.width.max(1) as usize)
+            .map(|row| {
+                let line: String = row.iter().map(|cell| cell.character).collect();
+                line.trim_end().to_string()
+            })
+            .collect::<Vec<_>>()
+            .join("\n")
+    })
+}
+
+pub fn cell(x: i32, y: i32) -> Option<Cell> {
+    STATE.with(|state| {
+        let state = state.borrow();
+        state.index(x, y).map(|index| state.presented[index])
+    })
+}
+
+/// How many times `print_xy` has been called since the last `reset`.
+pub fn print_calls() -> usize {
+    STATE.with(|state| state.borrow().print_calls)
+}
+
+/// What the game has stored under `name`, if anything.
+pub fn blob(name: &str) -> Option<Vec<u8>> {
+    STATE.with(|state| state.borrow().blobs.get(name).cloned())
+}
+
+/// Everything the game has logged since the last `reset`, oldest first.
+pub fn log_lines() -> Vec<String> {
+    STATE.with(|state| state.borrow().log_lines.clone())
+}
+
+fn print_xy(x: i32, y: i32, text: &str) {
+    STATE.with(|state| {
+        let mut state = state.borrow_mut();
+        state.print_calls += 1;
+
+        let (fg, bg) = (state.fg, state.bg);
+        for (i, character) in text.chars().enumerate() {
+            if let Some(index) = state.index(x + i as i32, y) {
+                state.cells[index] = Cell {
+                    character,
+                    fg,
+                    bg,
+                };
+            }
+        }
+    });
+}
+
+fn clear(area: Option<Rect>) {
+    STATE.with(|state| {
+        let mut state = state.borrow_mut();
+
+        let (x, y, width, height) = match area {
+            Some(rect) => (rect.top_left.x, rect.top_left.y, rect.size.width, rect.size.height),
+            None => (0, 0, state.size.width, state.size.height),
+        };
+
+        let bg = state.bg;
+        for cell_y in y..y + height {
+            for cell_x in x..x + width {
+                if let Some(index) = state.index(cell_x, cell_y) {
+                    state.cells[index] = Cell { bg, ..BLANK };
+                }
+            }
+        }
+    });
+}
+
+fn size() -> Size {
+    STATE.with(|state| state.borrow().size)
+}
+
+fn mouse_position() -> Point {
+    STATE.with(|state| state.borrow().mouse_position)
+}
+
+fn clicks() -> i32 {
+    if key_pressed(KeyCode::MouseLeft) { 1 } else { 0 }
+}
+
+fn key_pressed(key: KeyCode) -> bool {
+    STATE.with(|state| state.borrow().keys_down.contains(&key))
+}
+
+fn set_colors(fg: Color, bg: Color) {
+    STATE.with(|state| {
+        let mut state = state.borrow_mut();
+        state.fg = (fg.red, fg.green, fg.blue, fg.alpha);
+        state.bg = (bg.red, bg.green, bg.blue, bg.alpha);
+    });
+}
+
+fn read_blob(name: &str) -> Result<Option<Vec<u8>>, String> {
+    storage::check_name(name)?;
+
+    Ok(blob(name))
+}
+
+fn write_blob(name: &str, bytes: &[u8]) -> Result<(), String> {
+    storage::check_name(name)?;
+
+    STATE.with(|state| state.borrow_mut().blobs.insert(name.to_string(), bytes.to_vec()));
+
+    Ok(())
+}
+
+fn log(line: &str) {
+    STATE.with(|state| state.borrow_mut().log_lines.push(line.to_string()));
+}
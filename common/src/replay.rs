@@ -0,0 +1,690 @@
+//Everything the game sees of the outside world each frame is its events and
+//what it asks the `Platform` about the mouse, the keys and the window size.
+//Since the game's RNG is seeded deterministically, recording those for every
+//frame is enough to play a whole session back exactly. The game is updated
+//once per tick of the `clock`, so that is also what a frame is, and the time
+//the game sees comes from counting them.
+//
+//Replays are stored as text. After the header comes the seed the game was
+//started with, and today's seed at the time, then the key bindings in the
+//`bindings` module's format, and then one `frame` line per frame with that
+//frame's events, and any blobs the game read from storage during it, on
+//indented lines below it:
+//
+//    palimpsest-replay 1
+//    seed 42 daily 20261019
+//    bind Quit = Escape
+//    bind Reset = Ctrl+R
+//    ...
+//    frame 14 23 80x30
+//      MouseMove 14 23
+//    frame 14 23 80x30 MouseLeft
+//      KeyPressed MouseLeft
+//      read recent-seeds 34320A
+//    idle 120
+//
+//The numbers after `frame` are the mouse position and then the window size,
+//followed by any keys that were held down. `idle N` stands for N frames in a
+//row without events where nothing else changed either. A `read` line has the
+//blob's name and then its bytes in hex, `none` if there wasn't one, or
+//`error` and the message if reading it failed. Playing back hands the game
+//what was read when recording, so storage doesn't have to be the same.
+//
+//Changes to the bindings part way through aren't recorded, so the host doesn't
+//reload them while recording.
+
+use std::cell::RefCell;
+use std::fmt;
+use std::io::{self, Write};
+
+use bindings::Bindings;
+use {clock, ALL_KEY_CODES, Color, DEFAULT_SEED, Event, KeyCode, Platform, Point, Rect, Seed, Size};
+
+const HEADER: &str = "palimpsest-replay 1";
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Replay {
+    pub seed: Seed,
+    pub daily_seed: Seed,
+    pub bindings: Bindings,
+    pub frames: Vec<Frame>,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Frame {
+    pub mouse_position: Point,
+    pub size: Size,
+    pub keys_down: Vec<KeyCode>,
+    pub events: Vec<Event>,
+    /// The blobs the game read during the frame, in order.
+    pub reads: Vec<BlobRead>,
+}
+
+/// A blob the game read from storage, and what it got back.
+#[derive(Clone, Debug, PartialEq)]
+pub struct BlobRead {
+    pub name: String,
+    pub result: Result<Option<Vec<u8>>, String>,
+}
+
+impl Frame {
+    fn same_state_as(&self, other: &Frame) -> bool {
+        self.mouse_position == other.mouse_position && self.size == other.size &&
+        self.keys_down == other.keys_down
+    }
+}
+
+/// Records what `platform` currently reports, along with this frame's events.
+/// What the game reads during the frame is added by `take_reads` once it has
+/// been updated.
+pub fn capture(platform: &Platform, events: &[Event]) -> Frame {
+    Frame {
+        mouse_position: (platform.mouse_position)(),
+        size: (platform.size)(),
+        keys_down: ALL_KEY_CODES.iter().cloned().filter(|&key| (platform.key_pressed)(key)).collect(),
+        events: events.to_vec(),
+        reads: Vec::new(),
+    }
+}
+
+/// Writes frames as they happen, so a recording survives the program being
+/// killed part way through.
+pub struct Recorder<W: Write> {
+    output: W,
+    previous: Option<Frame>,
+    idle_frames: usize,
+}
+
+impl<W: Write> Recorder<W> {
+    pub fn new(mut output: W,
+               seed: Seed,
+               daily_seed: Seed,
+               bindings: &Bindings)
+               -> io::Result<Self> {
+        writeln!(output, "{}", HEADER)?;
+        writeln!(output, "seed {} daily {}", seed, daily_seed)?;
+        for line in bindings.to_string().lines() {
+            writeln!(output, "bind {}", line)?;
+        }
+
+        Ok(Recorder {
+            output,
+            previous: None,
+            idle_frames: 0,
+        })
+    }
+
+    pub fn record(&mut self, frame: Frame) -> io::Result<()> {
+        let idle = frame.events.is_empty() && frame.reads.is_empty() &&
+                   self.previous.as_ref().is_some_and(|previous| frame.same_state_as(previous));
+
+        if idle {
+            self.idle_frames += 1;
+            return Ok(());
+        }
+
+        self.write_idle_frames()?;
+        write!(self.output, "{}", frame)?;
+        self.output.flush()?;
+
+        self.previous = Some(frame);
+
+        Ok(())
+    }
+
+    pub fn finish(mut self) -> io::Result<W> {
+        self.write_idle_frames()?;
+        self.output.flush()?;
+
+        Ok(self.output)
+    }
+
+    fn write_idle_frames(&mut self) -> io::Result<()> {
+        if self.idle_frames > 0 {
+            writeln!(self.output, "idle {}", self.idle_frames)?;
+            self.idle_frames = 0;
+        }
+
+        Ok(())
+    }
+}
+
+impl fmt::Display for Frame {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f,
+               "frame {} {} {}",
+               self.mouse_position.x,
+               self.mouse_position.y,
+               self.size)?;
+        for key in self.keys_down.iter() {
+            write!(f, " {:?}", key)?;
+        }
+        writeln!(f)?;
+
+        for event in self.events.iter() {
+            writeln!(f, "  {}", EventText(*event))?;
+        }
+
+        for read in self.reads.iter() {
+            write!(f, "  read {} ", read.name)?;
+            match read.result {
+                Ok(Some(ref bytes)) => {
+                    for byte in bytes.iter() {
+                        write!(f, "{:02X}", byte)?;
+                    }
+                    //an empty blob still needs something there.
+                    if bytes.is_empty() {
+                        write!(f, "empty")?;
+                    }
+                }
+                Ok(None) => write!(f, "none")?,
+                //the message is the rest of the line.
+                Err(ref message) => write!(f, "error {}", message.replace('\n', " "))?,
+            }
+            writeln!(f)?;
+        }
+
+        Ok(())
+    }
+}
+
+struct EventText(Event);
+
+impl fmt::Display for EventText {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self.0 {
+            Event::Close => write!(f, "Close"),
+            Event::Resize { width, height } => write!(f, "Resize {} {}", width, height),
+            Event::MouseMove { x, y } => write!(f, "MouseMove {} {}", x, y),
+            Event::MouseScroll { delta } => write!(f, "MouseScroll {}", delta),
+            Event::KeyPressed { key, ctrl, shift } => {
+                write!(f, "KeyPressed {:?}{}", key, modifier_text(ctrl, shift))
+            }
+            Event::KeyReleased { key, ctrl, shift } => {
+                write!(f, "KeyReleased {:?}{}", key, modifier_text(ctrl, shift))
+            }
+            Event::ShiftPressed => write!(f, "ShiftPressed"),
+            Event::ShiftReleased => write!(f, "ShiftReleased"),
+            Event::ControlPressed => write!(f, "ControlPressed"),
+            Event::ControlReleased => write!(f, "ControlReleased"),
+        }
+    }
+}
+
+fn modifier_text(ctrl: bool, shift: bool) -> &'static str {
+    match (ctrl, shift) {
+        (false, false) => "",
+        (true, false) => " ctrl",
+        (false, true) => " shift",
+        (true, true) => " ctrl shift",
+    }
+}
+
+fn parse_number(text: Option<&str>) -> Result<i32, String> {
+    let text = text.ok_or_else(|| "missing number".to_string())?;
+    text.parse().map_err(|_| format!("\"{}\" is not a number", text))
+}
+
+fn parse_event(line: &str) -> Result<Event, String> {
+    let mut parts = line.split_whitespace();
+    let name = parts.next().unwrap_or("");
+
+    let event = match name {
+        "Close" => Event::Close,
+        "Resize" => {
+            Event::Resize {
+                width: parse_number(parts.next())?,
+                height: parse_number(parts.next())?,
+            }
+        }
+        "MouseMove" => {
+            Event::MouseMove {
+                x: parse_number(parts.next())?,
+                y: parse_number(parts.next())?,
+            }
+        }
+        "MouseScroll" => Event::MouseScroll { delta: parse_number(parts.next())? },
+        "KeyPressed" | "KeyReleased" => {
+            let key = parts.next().ok_or_else(|| "missing key".to_string())?.parse()?;
+            let modifiers: Vec<&str> = parts.by_ref().collect();
+            let ctrl = modifiers.contains(&"ctrl");
+            let shift = modifiers.contains(&"shift");
+
+            if name == "KeyPressed" {
+                Event::KeyPressed {
+                    key,
+                    ctrl,
+                    shift,
+                }
+            } else {
+                Event::KeyReleased {
+                    key,
+                    ctrl,
+                    shift,
+                }
+            }
+        }
+        "ShiftPressed" => Event::ShiftPressed,
+        "ShiftReleased" => Event::ShiftReleased,
+        "ControlPressed" => Event::ControlPressed,
+        "ControlReleased" => Event::ControlReleased,
+        other => return Err(format!("\"{}\" is not an event", other)),
+    };
+
+    match parts.next() {
+        Some(extra) => Err(format!("unexpected \"{}\"", extra)),
+        None => Ok(event),
+    }
+}
+
+fn parse_read(line: &str) -> Result<BlobRead, String> {
+    let mut parts = line.splitn(3, ' ').skip(1);
+    let name = parts.next().unwrap_or("");
+    let value = parts.next().ok_or_else(|| "missing blob".to_string())?;
+
+    let result = match value {
+        "none" => Ok(None),
+        "empty" => Ok(Some(Vec::new())),
+        _ if value.starts_with("error") => Err(value["error".len()..].trim().to_string()),
+        _ => {
+            let digits = value.as_bytes();
+            if digits.len() % 2 != 0 {
+                return Err(format!("\"{}\" should have two hex digits per byte", value));
+            }
+
+            let bytes = digits.chunks(2)
+                .map(|pair| {
+                    ::std::str::from_utf8(pair)
+                        .ok()
+                        .and_then(|pair| u8::from_str_radix(pair, 16).ok())
+                })
+                .collect::<Option<Vec<u8>>>()
+                .ok_or_else(|| format!("\"{}\" is not hex", value))?;
+
+            Ok(Some(bytes))
+        }
+    };
+
+    Ok(BlobRead {
+        name: name.to_string(),
+        result,
+    })
+}
+
+fn parse_frame_line(line: &str) -> Result<Frame, String> {
+    let mut parts = line.split_whitespace().skip(1);
+
+    let x = parse_number(parts.next())?;
+    let y = parse_number(parts.next())?;
+
+    let size_text = parts.next().ok_or_else(|| "missing size".to_string())?;
+    let mut size_parts = size_text.split('x');
+    let width = parse_number(size_parts.next())?;
+    let height = parse_number(size_parts.next())?;
+
+    let mut keys_down = Vec::new();
+    for key in parts {
+        keys_down.push(key.parse()?);
+    }
+
+    Ok(Frame {
+        mouse_position: Point::new_safe(x, y),
+        size: Size::new(width.max(0), height.max(0)),
+        keys_down,
+        events: Vec::new(),
+        reads: Vec::new(),
+    })
+}
+
+fn parse_seed(text: Option<&str>) -> Result<Seed, String> {
+    let text = text.ok_or_else(|| "missing seed".to_string())?;
+    text.parse().map_err(|_| format!("\"{}\" is not a seed", text))
+}
+
+fn parse_seed_line(line: &str) -> Result<(Seed, Seed), String> {
+    let mut parts = line.split_whitespace().skip(1);
+
+    let seed = parse_seed(parts.next())?;
+    let daily_seed = match parts.next() {
+        Some("daily") => parse_seed(parts.next())?,
+        Some(other) => return Err(format!("unexpected \"{}\"", other)),
+        None => seed,
+    };
+
+    Ok((seed, daily_seed))
+}
+
+/// Reads a replay written by `Recorder`, with idle frames expanded. Replays
+/// without a `seed` line are taken to have used `DEFAULT_SEED`, and ones
+/// without `bind` lines the default bindings.
+pub fn parse(text: &str) -> Result<Replay, String> {
+    let mut lines = text.lines().enumerate();
+
+    match lines.next() {
+        Some((_, line)) if line.trim() == HEADER => {}
+        _ => return Err(format!("a replay should start with \"{}\"", HEADER)),
+    }
+
+    let mut seeds = (DEFAULT_SEED, DEFAULT_SEED);
+    let mut bindings_text = String::new();
+    let mut frames: Vec<Frame> = Vec::new();
+
+    for (line_index, line) in lines {
+        let error = |message: String| format!("line {}: {}", line_index + 1, message);
+        let trimmed = line.trim();
+
+        if trimmed.is_empty() {
+            continue;
+        } else if trimmed.starts_with("seed") {
+            if !frames.is_empty() {
+                return Err(error("the seed should come before the first frame".to_string()));
+            }
+
+            seeds = parse_seed_line(trimmed).map_err(&error)?;
+        } else if let Some(binding) = trimmed.strip_prefix("bind") {
+            if !frames.is_empty() {
+                return Err(error("bindings should come before the first frame".to_string()));
+            }
+
+            bindings_text.push_str(binding);
+            bindings_text.push('\n');
+        } else if trimmed.starts_with("frame") {
+            frames.push(parse_frame_line(trimmed).map_err(&error)?);
+        } else if trimmed.starts_with("idle") {
+            let count = parse_number(trimmed.split_whitespace().nth(1)).map_err(&error)?;
+            let idle_frame = match frames.last() {
+                Some(previous) => {
+                    Frame {
+                        events: Vec::new(),
+                        reads: Vec::new(),
+                        ..previous.clone()
+                    }
+                }
+                None => return Err(error("idle frames before the first frame".to_string())),
+            };
+
+            for _ in 0..count {
+                frames.push(idle_frame.clone());
+            }
+        } else if trimmed.starts_with("read ") {
+            let read = parse_read(trimmed).map_err(&error)?;
+
+            match frames.last_mut() {
+                Some(frame) => frame.reads.push(read),
+                None => return Err(error("a read before the first frame".to_string())),
+            }
+        } else {
+            let event = parse_event(trimmed).map_err(&error)?;
+
+            match frames.last_mut() {
+                Some(frame) => frame.events.push(event),
+                None => return Err(error("an event before the first frame".to_string())),
+            }
+        }
+    }
+
+    Ok(Replay {
+        seed: seeds.0,
+        daily_seed: seeds.1,
+        //actions added since the replay was recorded get their default keys.
+        bindings: Bindings::parse(&bindings_text).0,
+        frames,
+    })
+}
+
+//Playing back needs a `Platform` that draws like the real one but reports the
+//recorded input instead. Since `Platform` only holds function pointers, the
+//real platform and the current frame are kept in a thread local.
+struct Playback {
+    output: Platform,
+    frame: Frame,
+    //how many of the frame's reads have been handed out.
+    reads_served: usize,
+}
+
+thread_local! {
+    static PLAYBACK: RefCell<Option<Playback>> = const { RefCell::new(None) };
+}
+
+/// A `Platform` that draws with `output` and reports the input of whichever
+/// frame was last passed to `set_frame`.
+pub fn playback_platform(output: &Platform) -> Platform {
+    let size = (output.size)();
+
+    PLAYBACK.with(|playback| {
+        *playback.borrow_mut() = Some(Playback {
+            output: *output,
+            frame: Frame {
+                mouse_position: Point::new(0, 0),
+                size,
+                keys_down: Vec::new(),
+                events: Vec::new(),
+                reads: Vec::new(),
+            },
+            reads_served: 0,
+        })
+    });
+
+    Platform {
+        print_xy,
+        clear,
+        size: size_of_frame,
+        mouse_position,
+        clicks,
+        key_pressed,
+        set_colors,
+        time: clock::time,
+        read_blob,
+        write_blob,
+        log,
+    }
+}
+
+pub fn set_frame(frame: &Frame) {
+    PLAYBACK.with(|playback| {
+        if let Some(ref mut playback) = *playback.borrow_mut() {
+            playback.frame = frame.clone();
+            playback.reads_served = 0;
+        }
+    });
+}
+
+fn output() -> Platform {
+    PLAYBACK.with(|playback| {
+        playback.borrow()
+            .as_ref()
+            .map(|playback| playback.output)
+            .expect("playback_platform should be called before playing back")
+    })
+}
+
+fn with_frame<T, F: FnOnce(&Frame) -> T>(f: F) -> T {
+    PLAYBACK.with(|playback| {
+        f(&playback.borrow()
+            .as_ref()
+            .expect("playback_platform should be called before playing back")
+            .frame)
+    })
+}
+
+fn print_xy(x: i32, y: i32, text: &str) {
+    (output().print_xy)(x, y, text)
+}
+
+fn clear(area: Option<Rect>) {
+    (output().clear)(area)
+}
+
+fn set_colors(fg: Color, bg: Color) {
+    (output().set_colors)(fg, bg)
+}
+
+//hands out the frame's next recorded read if it is of `name`. Replays
+//recorded before reads were, or ones the game has strayed from, read from
+//storage instead.
+fn read_blob(name: &str) -> Result<Option<Vec<u8>>, String> {
+    let recorded = PLAYBACK.with(|playback| {
+        let mut playback = playback.borrow_mut();
+        let playback = playback.as_mut()
+            .expect("playback_platform should be called before playing back");
+
+        let read = playback.frame.reads.get(playback.reads_served).cloned();
+        match read {
+            Some(read) if read.name == name => {
+                playback.reads_served += 1;
+                Some(read.result)
+            }
+            _ => None,
+        }
+    });
+
+    recorded.unwrap_or_else(|| (output().read_blob)(name))
+}
+
+fn write_blob(name: &str, bytes: &[u8]) -> Result<(), String> {
+    (output().write_blob)(name, bytes)
+}
+
+fn log(line: &str) {
+    (output().log)(line)
+}
+
+fn size_of_frame() -> Size {
+    with_frame(|frame| frame.size)
+}
+
+fn mouse_position() -> Point {
+    with_frame(|frame| frame.mouse_position)
+}
+
+fn clicks() -> i32 {
+    if key_pressed(KeyCode::MouseLeft) { 1 } else { 0 }
+}
+
+fn key_pressed(key: KeyCode) -> bool {
+    with_frame(|frame| frame.keys_down.contains(&key))
+}
+
+//Recording what the game reads works the same way, with a `Platform` that
+//passes everything on to the real one and keeps a copy of each read.
+struct Recording {
+    output: Platform,
+    reads: Vec<BlobRead>,
+}
+
+thread_local! {
+    static RECORDING: RefCell<Option<Recording>> = const { RefCell::new(None) };
+}
+
+/// A `Platform` that is `output`, except that blobs read through it are kept
+/// for `take_reads`.
+pub fn recording_platform(output: &Platform) -> Platform {
+    RECORDING.with(|recording| {
+        *recording.borrow_mut() = Some(Recording {
+            output: *output,
+            reads: Vec::new(),
+        })
+    });
+
+    Platform {
+        read_blob: read_and_record_blob,
+        ..*output
+    }
+}
+
+/// The blobs read through the `recording_platform` since the last call, to go
+/// in the frame they were read during.
+pub fn take_reads() -> Vec<BlobRead> {
+    RECORDING.with(|recording| {
+        recording.borrow_mut()
+            .as_mut()
+            .map(|recording| ::std::mem::take(&mut recording.reads))
+            .unwrap_or_default()
+    })
+}
+
+fn read_and_record_blob(name: &str) -> Result<Option<Vec<u8>>, String> {
+    let output = RECORDING.with(|recording| {
+        recording.borrow()
+            .as_ref()
+            .map(|recording| recording.output)
+            .expect("recording_platform should be called before recording")
+    });
+
+    let result = (output.read_blob)(name);
+
+    RECORDING.with(|recording| {
+        if let Some(ref mut recording) = *recording.borrow_mut() {
+            recording.reads.push(BlobRead {
+                name: name.to_string(),
+                result: result.clone(),
+            });
+        }
+    });
+
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use recording;
+
+    fn read(name: &str, result: Result<Option<Vec<u8>>, String>) -> BlobRead {
+        BlobRead {
+            name: name.to_string(),
+            result,
+        }
+    }
+
+    fn replay_with_reads(reads: Vec<BlobRead>) -> Replay {
+        let mut frame = capture(&recording::platform(), &[]);
+        frame.reads = reads;
+
+        let mut recorder = Recorder::new(Vec::new(), 7, 8, &Bindings::default()).unwrap();
+        recorder.record(frame).unwrap();
+        let text = String::from_utf8(recorder.finish().unwrap()).unwrap();
+
+        parse(&text).unwrap()
+    }
+
+    #[test]
+    fn reads_survive_being_written_out() {
+        let reads = vec![read("recent-seeds", Ok(Some(b"42\n7\n".to_vec()))),
+                         read("empty", Ok(Some(Vec::new()))),
+                         read("missing", Ok(None)),
+                         read("broken", Err("permission denied".to_string()))];
+
+        let replay = replay_with_reads(reads.clone());
+
+        assert_eq!(replay.frames[0].reads, reads);
+    }
+
+    #[test]
+    fn playback_reads_what_was_recorded() {
+        recording::reset(Size::new(80, 30));
+        let output = recording::platform();
+        (output.write_blob)("progress", b"stored now").unwrap();
+
+        let replay = replay_with_reads(vec![read("progress", Ok(Some(b"recorded".to_vec())))]);
+        let platform = playback_platform(&output);
+        set_frame(&replay.frames[0]);
+
+        assert_eq!((platform.read_blob)("progress"), Ok(Some(b"recorded".to_vec())));
+        //anything not recorded comes from storage.
+        assert_eq!((platform.read_blob)("progress"), Ok(Some(b"stored now".to_vec())));
+    }
+
+    #[test]
+    fn recording_keeps_what_was_read() {
+        recording::reset(Size::new(80, 30));
+        let output = recording::platform();
+        (output.write_blob)("progress", b"saved").unwrap();
+
+        let platform = recording_platform(&output);
+        assert_eq!((platform.read_blob)("progress"), Ok(Some(b"saved".to_vec())));
+
+        assert_eq!(take_reads(), vec![read("progress", Ok(Some(b"saved".to_vec())))]);
+        assert_eq!(take_reads(), Vec::new());
+    }
+}
@@ -0,0 +1,421 @@
+//Hot reloading used to hand the same `Game` to the new library, which only
+//works while `Game` doesn't change. Instead the old library writes the game
+//out in this format before it is unloaded, and the new library reads it back
+//into whatever its `Game` looks like now.
+//
+//The format is text, one `key values...` line per piece of state:
+//
+//    palimpsest-game 1
+//    seed 42
+//    registers 2 4 8 16 0 0 0 0
+//    instruction 0x02 load 0x02 A
+//    layer 0x02 none NOP
+//    layer 0x02 3 1 1 2500 load 0x02 A
+//    cards 1
+//    card 14 23 add  0x12 A; NOP
+//
+//Loading starts from a fresh game and only changes what the text mentions, so
+//a field added since the text was written keeps its starting value, and keys
+//that are no longer known are skipped. Things that only matter for a frame or
+//two, like which button the mouse is over, aren't saved at all.
+
+use std::fmt::Write;
+use std::time::Duration;
+
+use rand::{Rng, SeedableRng, StdRng};
+
+use bindings::Bindings;
+use history::{CardId, Layer, Placement};
+use profile::Loop;
+use theme::Theme;
+use {Card, Game, Instruction, NewGameScreen, Point, PLAYFIELD_SIZE, REGISTER_AMOUNT};
+
+const HEADER: &str = "palimpsest-game";
+
+/// Bump this when a key changes meaning, so `load` can tell the two apart.
+/// Adding or removing keys doesn't need a new version.
+pub const FORMAT_VERSION: u32 = 1;
+
+pub fn save(game: &Game) -> String {
+    let mut text = String::new();
+
+    //writing to a `String` can't fail.
+    let _ = write_game(&mut text, game);
+
+    text
+}
+
+fn write_game(text: &mut String, game: &Game) -> ::std::fmt::Result {
+    writeln!(text, "{} {}", HEADER, FORMAT_VERSION)?;
+
+    writeln!(text, "seed {}", game.seed)?;
+    writeln!(text, "daily-seed {}", game.daily_seed)?;
+
+    //`StdRng` can't be written out, so the loaded game gets a new one seeded
+    //from this one.
+    let mut rng = game.rng;
+    writeln!(text, "rng {} {}", rng.gen::<u32>(), rng.gen::<u32>())?;
+
+    writeln!(text, "scroll-offset {}", game.scroll_offset)?;
+    writeln!(text, "paused {}", game.paused)?;
+    writeln!(text, "executing-address {}", optional(game.executing_address))?;
+    writeln!(text, "instruction-countdown {}", game.instruction_countdown)?;
+    writeln!(text, "registers {}", list(game.registers.iter()))?;
+    writeln!(text, "trace {}", list(game.trace.iter()))?;
+    writeln!(text, "profile-steps {}", game.profile.steps)?;
+    writeln!(text, "profile-counts {}", list(game.profile.counts.iter()))?;
+    for l in game.profile.loops.iter() {
+        writeln!(text, "profile-loop {} {} {} {}", l.start, l.end, l.iterations, l.steps)?;
+    }
+
+    for (address, instruction) in game.instructions.iter().enumerate() {
+        writeln!(text, "instruction {:#04X} {}", address, instruction)?;
+    }
+    //bottom first, with the card, player, turn and time in milliseconds of
+    //each placed layer.
+    for address in 0..PLAYFIELD_SIZE {
+        for layer in game.history.layers(address) {
+            match layer.placement {
+                Some(placement) => {
+                    writeln!(text,
+                             "layer {:#04X} {} {} {} {} {}",
+                             address,
+                             placement.card,
+                             placement.player,
+                             placement.turn,
+                             placement.time.as_millis(),
+                             layer.instruction)?
+                }
+                None => writeln!(text, "layer {:#04X} none {}", address, layer.instruction)?,
+            }
+        }
+    }
+    writeln!(text, "turn {}", game.turn)?;
+    writeln!(text, "player {}", game.player)?;
+    writeln!(text, "peeled-layers {}", game.peeled_layers)?;
+
+    writeln!(text, "next-card-id {}", game.next_card_id)?;
+    writeln!(text, "cards {}", game.cards.len())?;
+    for card in game.cards.iter() {
+        writeln!(text,
+                 "card {} {} {}",
+                 card.location.x,
+                 card.location.y,
+                 instruction_list(&card.instructions))?;
+    }
+    writeln!(text, "card-ids {}", list(game.cards.iter().map(|card| card.id)))?;
+    writeln!(text, "selected-card {}", optional(game.selected_card))?;
+    writeln!(text, "placement-cursor {}", optional(game.placement_cursor))?;
+
+    writeln!(text, "editor-enabled {}", game.editor.enabled)?;
+    writeln!(text, "editor-address {}", optional(game.editor.address))?;
+    writeln!(text, "editor-field {}", game.editor.field)?;
+    writeln!(text, "editor-selection-end {}", optional(game.editor.selection_end))?;
+    writeln!(text, "clipboard {}", instruction_list(&game.editor.clipboard))?;
+
+    if let Some(ref screen) = game.new_game_screen {
+        writeln!(text, "new-game-screen {}", screen.seed_text)?;
+    }
+
+    for line in game.bindings.to_string().lines() {
+        writeln!(text, "bind {}", line)?;
+    }
+    for line in game.theme.to_string().lines() {
+        writeln!(text, "theme {}", line)?;
+    }
+
+    Ok(())
+}
+
+fn optional<T: ToString>(value: Option<T>) -> String {
+    value.map_or_else(|| "none".to_string(), |value| value.to_string())
+}
+
+fn list<T: ToString, I: Iterator<Item = T>>(values: I) -> String {
+    values.map(|value| value.to_string()).collect::<Vec<_>>().join(" ")
+}
+
+fn instruction_list(instructions: &[Instruction]) -> String {
+    instructions.iter().map(|instruction| instruction.to_string()).collect::<Vec<_>>().join("; ")
+}
+
+/// Applies the state in `text` to `game`, which should be a new game of the
+/// right size. Anything `text` doesn't mention is left as it is in `game`.
+/// Whether `text` starts like a saved game does, rather than being some other
+/// format.
+pub fn is_save(text: &str) -> bool {
+    text.split_whitespace().next() == Some(HEADER)
+}
+
+pub fn load(text: &str, mut game: Game) -> Result<Game, String> {
+    let mut lines = text.lines().enumerate();
+
+    match lines.next().map(|(_, line)| line.split_whitespace().collect::<Vec<_>>()) {
+        Some(ref parts) if parts.len() == 2 && parts[0] == HEADER => {
+            let version = parts[1]
+                .parse::<u32>()
+                .map_err(|_| format!("\"{}\" is not a format version", parts[1]))?;
+
+            //there has only been one version so far, so there is nothing to
+            //migrate from.
+            if version != FORMAT_VERSION {
+                return Err(format!("this is format version {}, but only version {} can be loaded",
+                                   version,
+                                   FORMAT_VERSION));
+            }
+        }
+        _ => return Err(format!("a saved game should start with \"{} VERSION\"", HEADER)),
+    }
+
+    let mut expected_cards = None;
+    //`bind` and `theme` lines are in the bindings and theme file formats, so
+    //each kind is read together.
+    let mut bindings_text = String::new();
+    let mut theme_text = String::new();
+    //the layers at each address, bottom first.
+    let mut layers: Vec<Vec<Layer>> = vec![Vec::new(); PLAYFIELD_SIZE];
+    let mut card_ids = None;
+
+    for (line_index, line) in lines {
+        let error = |message: String| format!("line {}: {}", line_index + 1, message);
+
+        let line = line.trim();
+        let (key, value) = match line.find(' ') {
+            Some(index) => (&line[..index], line[index + 1..].trim()),
+            None => (line, ""),
+        };
+
+        match key {
+            "seed" => game.seed = parse(value).map_err(&error)?,
+            "daily-seed" => game.daily_seed = parse(value).map_err(&error)?,
+            "rng" => {
+                let seeds = value.split_whitespace()
+                    .map(parse::<usize>)
+                    .collect::<Result<Vec<_>, _>>()
+                    .map_err(&error)?;
+                let rng: StdRng = SeedableRng::from_seed(&seeds[..]);
+                game.rng = rng;
+            }
+            "scroll-offset" => game.scroll_offset = parse(value).map_err(&error)?,
+            "paused" => game.paused = parse(value).map_err(&error)?,
+            "executing-address" => game.executing_address = parse_optional(value).map_err(&error)?,
+            "instruction-countdown" => game.instruction_countdown = parse(value).map_err(&error)?,
+            "registers" => {
+                let registers = value.split_whitespace()
+                    .map(parse::<u8>)
+                    .collect::<Result<Vec<_>, _>>()
+                    .map_err(&error)?;
+                if registers.len() != REGISTER_AMOUNT {
+                    return Err(error(format!("expected {} registers", REGISTER_AMOUNT)));
+                }
+                game.registers.copy_from_slice(&registers);
+            }
+            "trace" => {
+                game.trace = value.split_whitespace()
+                    .map(parse::<usize>)
+                    .collect::<Result<Vec<_>, _>>()
+                    .map_err(&error)?;
+            }
+            "profile-steps" => game.profile.steps = parse(value).map_err(&error)?,
+            "profile-counts" => {
+                let counts = value.split_whitespace()
+                    .map(parse::<u64>)
+                    .collect::<Result<Vec<_>, _>>()
+                    .map_err(&error)?;
+                if counts.len() != PLAYFIELD_SIZE {
+                    return Err(error(format!("expected {} counts", PLAYFIELD_SIZE)));
+                }
+                game.profile.counts.copy_from_slice(&counts);
+            }
+            "profile-loop" => {
+                let values = value.split_whitespace()
+                    .map(parse::<u64>)
+                    .collect::<Result<Vec<_>, _>>()
+                    .map_err(&error)?;
+                match values[..] {
+                    [start, end, iterations, steps] if start <= end &&
+                                                       end < PLAYFIELD_SIZE as u64 => {
+                        game.profile.loops.push(Loop {
+                            start: start as usize,
+                            end: end as usize,
+                            iterations,
+                            steps,
+                        })
+                    }
+                    _ => return Err(error("expected START END ITERATIONS STEPS".to_string())),
+                }
+            }
+            "instruction" => {
+                let index = value.find(' ').unwrap_or(value.len());
+                let address = parse_address(&value[..index]).map_err(&error)?;
+                if address >= PLAYFIELD_SIZE {
+                    return Err(error(format!("address {:#04X} is off the playfield", address)));
+                }
+                game.instructions[address] = value[index..].parse().map_err(&error)?;
+            }
+            "layer" => {
+                let mut parts = value.splitn(2, ' ');
+                let address = parse_address(parts.next().unwrap_or("")).map_err(&error)?;
+                if address >= PLAYFIELD_SIZE {
+                    return Err(error(format!("address {:#04X} is off the playfield", address)));
+                }
+                let rest = parts.next().unwrap_or("");
+
+                let (placement, instruction) = match rest.strip_prefix("none ") {
+                    Some(instruction) => (None, instruction),
+                    None => {
+                        let mut parts = rest.splitn(5, ' ');
+                        let mut next = || parts.next().unwrap_or("");
+                        let placement = Placement {
+                            card: parse(next()).map_err(&error)?,
+                            player: parse(next()).map_err(&error)?,
+                            turn: parse(next()).map_err(&error)?,
+                            time: Duration::from_millis(parse(next()).map_err(&error)?),
+                        };
+
+                        (Some(placement), next())
+                    }
+                };
+
+                layers[address].push(Layer {
+                    instruction: instruction.parse().map_err(&error)?,
+                    placement,
+                });
+            }
+            "turn" => game.turn = parse(value).map_err(&error)?,
+            "player" => game.player = parse(value).map_err(&error)?,
+            "peeled-layers" => game.peeled_layers = parse(value).map_err(&error)?,
+            "next-card-id" => game.next_card_id = parse(value).map_err(&error)?,
+            "cards" => {
+                expected_cards = Some(parse::<usize>(value).map_err(&error)?);
+                game.cards.clear();
+            }
+            "card" => {
+                let mut parts = value.splitn(3, ' ');
+                let x = parse(parts.next().unwrap_or("")).map_err(&error)?;
+                let y = parse(parts.next().unwrap_or("")).map_err(&error)?;
+                let instructions = parse_instruction_list(parts.next().unwrap_or(""))
+                    .map_err(&error)?;
+
+                //numbered after the rest are read, see below.
+                game.cards.push(Card {
+                    id: 0,
+                    location: Point::new_safe(x, y),
+                    instructions,
+                });
+            }
+            "card-ids" => {
+                card_ids = Some(value.split_whitespace()
+                    .map(parse::<CardId>)
+                    .collect::<Result<Vec<_>, _>>()
+                    .map_err(&error)?);
+            }
+            "selected-card" => game.selected_card = parse_optional(value).map_err(&error)?,
+            "placement-cursor" => {
+                game.placement_cursor = parse_optional(value).map_err(&error)?
+            }
+            "editor-enabled" => game.editor.enabled = parse(value).map_err(&error)?,
+            "editor-address" => game.editor.address = parse_optional(value).map_err(&error)?,
+            "editor-field" => game.editor.field = parse(value).map_err(&error)?,
+            "editor-selection-end" => {
+                game.editor.selection_end = parse_optional(value).map_err(&error)?
+            }
+            "clipboard" => game.editor.clipboard = parse_instruction_list(value).map_err(&error)?,
+            "new-game-screen" => {
+                game.new_game_screen = Some(NewGameScreen {
+                    seed_text: value.to_string(),
+                    ..NewGameScreen::default()
+                })
+            }
+            "bind" => {
+                bindings_text.push_str(value);
+                bindings_text.push('\n');
+            }
+            "theme" => {
+                theme_text.push_str(value);
+                theme_text.push('\n');
+            }
+            _ => {}
+        }
+    }
+
+    if !bindings_text.is_empty() {
+        //actions added since the game was saved get their default keys.
+        game.bindings = Bindings::parse(&bindings_text).0;
+    }
+    if !theme_text.is_empty() {
+        game.theme = Theme::parse(&theme_text).0;
+    }
+
+    if let Some(expected) = expected_cards {
+        if expected != game.cards.len() {
+            return Err(format!("expected {} cards but found {}", expected, game.cards.len()));
+        }
+    }
+
+    //cards saved before they had ids get new ones.
+    match card_ids {
+        Some(ref ids) if ids.len() == game.cards.len() => {
+            for (card, &id) in game.cards.iter_mut().zip(ids.iter()) {
+                card.id = id;
+            }
+        }
+        _ => {
+            for card in game.cards.iter_mut() {
+                card.id = game.next_card_id;
+                game.next_card_id += 1;
+            }
+        }
+    }
+
+    for (address, layers) in layers.into_iter().enumerate() {
+        game.history.replace(address, layers);
+        //the playfield is what counts, if the two disagree.
+        game.history.overwrite(address, game.instructions[address]);
+    }
+    game.peeled_layers = game.peeled_layers.min(game.history.depth());
+
+    //anything pointing at something that isn't there any more is dropped.
+    if game.selected_card.is_some_and(|index| index >= game.cards.len()) {
+        game.selected_card = None;
+    }
+    if game.selected_card.is_none() ||
+       game.placement_cursor.is_some_and(|address| address >= PLAYFIELD_SIZE) {
+        game.placement_cursor = None;
+    }
+    if game.executing_address.is_some_and(|address| address < 0 || address >= PLAYFIELD_SIZE as i32) {
+        game.executing_address = None;
+    }
+    game.trace.retain(|&address| address < PLAYFIELD_SIZE);
+    if game.editor.selection().is_some_and(|(_, end)| end >= PLAYFIELD_SIZE) {
+        game.editor.address = None;
+        game.editor.selection_end = None;
+    }
+
+    Ok(game)
+}
+
+fn parse<T: ::std::str::FromStr>(text: &str) -> Result<T, String> {
+    text.parse().map_err(|_| format!("\"{}\" is not a valid value", text))
+}
+
+fn parse_optional<T: ::std::str::FromStr>(text: &str) -> Result<Option<T>, String> {
+    if text == "none" {
+        Ok(None)
+    } else {
+        parse(text).map(Some)
+    }
+}
+
+fn parse_address(text: &str) -> Result<usize, String> {
+    let digits = text.trim_start_matches("0x").trim_start_matches("0X");
+    usize::from_str_radix(digits, 16).map_err(|_| format!("\"{}\" is not an address", text))
+}
+
+fn parse_instruction_list(text: &str) -> Result<Vec<Instruction>, String> {
+    if text.trim().is_empty() {
+        return Ok(Vec::new());
+    }
+
+    text.split(';').map(|instruction| instruction.parse()).collect()
+}
@@ -0,0 +1,222 @@
+//Most of the screen looks the same from one frame to the next, so rather than
+//printing everything every frame, the game draws into a grid of cells and only
+//the cells that differ from the last frame are sent on to the real `Platform`.
+//`render` does that by handing the drawing code a `Platform` whose drawing
+//functions write into the grid, and whose other functions are the real ones,
+//so the drawing code doesn't need to know about any of this.
+//
+//This relies on the screen still showing the last frame when the next one is
+//drawn, so whoever else draws on it has to `invalidate` the `Screen` after,
+//to have the whole of the next frame sent.
+//
+//`Platform` is a table of plain function pointers, so the grid being drawn into
+//is kept in a thread local while `render` runs.
+
+use std::cell::RefCell;
+
+use {Color, Platform, Rect, Size};
+
+type Rgba = (u8, u8, u8, u8);
+
+const DEFAULT_FG: Rgba = (255, 255, 255, 255);
+const DEFAULT_BG: Rgba = (0, 0, 0, 255);
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+struct Cell {
+    character: char,
+    fg: Rgba,
+    bg: Rgba,
+}
+
+const BLANK: Cell = Cell {
+    character: ' ',
+    fg: DEFAULT_FG,
+    bg: DEFAULT_BG,
+};
+
+/// A frame's worth of cells, which is what was last sent to the real
+/// `Platform` when kept between frames.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Screen {
+    size: Size,
+    cells: Vec<Cell>,
+}
+
+impl Default for Screen {
+    /// A screen with nothing on it, so the next frame is sent in full.
+    fn default() -> Self {
+        Screen::new(Size::new(0, 0))
+    }
+}
+
+impl Screen {
+    fn new(size: Size) -> Self {
+        Screen {
+            size,
+            cells: vec![BLANK; (size.width.max(0) * size.height.max(0)) as usize],
+        }
+    }
+
+    /// Forgets what was sent, so the next frame is sent in full.
+    pub fn invalidate(&mut self) {
+        *self = Screen::default();
+    }
+
+    fn index(&self, x: i32, y: i32) -> Option<usize> {
+        if x < 0 || y < 0 || x >= self.size.width || y >= self.size.height {
+            None
+        } else {
+            Some((y * self.size.width + x) as usize)
+        }
+    }
+}
+
+struct Frame {
+    screen: Screen,
+    fg: Rgba,
+    bg: Rgba,
+}
+
+thread_local! {
+    static FRAME: RefCell<Option<Frame>> = const { RefCell::new(None) };
+}
+
+/// Calls `draw` with a `Platform` that draws into a new frame, then sends
+/// whatever differs from `previous` on to `output`. `previous` becomes the
+/// new frame, unless `draw` panics, in which case nothing is sent.
+pub fn render<T, F>(output: &Platform, previous: &mut Screen, draw: F) -> T
+    where F: FnOnce(&Platform) -> T
+{
+    FRAME.with(|frame| {
+        *frame.borrow_mut() = Some(Frame {
+            screen: Screen::new((output.size)()),
+            fg: DEFAULT_FG,
+            bg: DEFAULT_BG,
+        })
+    });
+
+    let buffered = Platform {
+        print_xy,
+        clear,
+        set_colors,
+        ..*output
+    };
+
+    let result = draw(&buffered);
+
+    let current = FRAME.with(|frame| frame.borrow_mut().take())
+        .expect("the frame should still be there after drawing")
+        .screen;
+
+    send_changes(output, previous, &current);
+    *previous = current;
+
+    result
+}
+
+//prints each run of changed cells in a row with one call, changing colours
+//only where they change.
+fn send_changes(output: &Platform, previous: &Screen, current: &Screen) {
+    let blank = Screen::new(current.size);
+    let previous = if previous.size == current.size {
+        previous
+    } else {
+        //nothing that was on the screen can be relied on.
+        (output.set_colors)(to_color(DEFAULT_FG), to_color(DEFAULT_BG));
+        (output.clear)(None);
+        &blank
+    };
+
+    let width = current.size.width.max(1) as usize;
+    let rows = current.cells.chunks(width).zip(previous.cells.chunks(width));
+    let mut colors = (DEFAULT_FG, DEFAULT_BG);
+
+    for (y, (row, previous_row)) in rows.enumerate() {
+        let mut x = 0;
+
+        while x < row.len() {
+            if row[x] == previous_row[x] {
+                x += 1;
+                continue;
+            }
+
+            let start = x;
+            let run_colors = (row[x].fg, row[x].bg);
+            let in_run = |x: usize| row[x] != previous_row[x] && (row[x].fg, row[x].bg) == run_colors;
+
+            let mut text = String::new();
+            while x < row.len() && in_run(x) {
+                text.push(row[x].character);
+                x += 1;
+            }
+
+            if colors != run_colors {
+                (output.set_colors)(to_color(run_colors.0), to_color(run_colors.1));
+                colors = run_colors;
+            }
+            (output.print_xy)(start as i32, y as i32, &text);
+        }
+    }
+
+    if colors != (DEFAULT_FG, DEFAULT_BG) {
+        (output.set_colors)(to_color(DEFAULT_FG), to_color(DEFAULT_BG));
+    }
+}
+
+fn with_frame<F: FnOnce(&mut Frame)>(f: F) {
+    FRAME.with(|frame| {
+        if let Some(ref mut frame) = *frame.borrow_mut() {
+            f(frame)
+        }
+    })
+}
+
+fn print_xy(x: i32, y: i32, text: &str) {
+    with_frame(|frame| {
+        let (fg, bg) = (frame.fg, frame.bg);
+
+        for (i, character) in text.chars().enumerate() {
+            if let Some(index) = frame.screen.index(x + i as i32, y) {
+                frame.screen.cells[index] = Cell {
+                    character,
+                    fg,
+                    bg,
+                };
+            }
+        }
+    })
+}
+
+fn clear(area: Option<Rect>) {
+    with_frame(|frame| {
+        let (x, y, width, height) = match area {
+            Some(rect) => (rect.top_left.x, rect.top_left.y, rect.size.width, rect.size.height),
+            None => (0, 0, frame.screen.size.width, frame.screen.size.height),
+        };
+
+        let bg = frame.bg;
+        for cell_y in y..y + height {
+            for cell_x in x..x + width {
+                if let Some(index) = frame.screen.index(cell_x, cell_y) {
+                    frame.screen.cells[index] = Cell { bg, ..BLANK };
+                }
+            }
+        }
+    })
+}
+
+fn set_colors(fg: Color, bg: Color) {
+    with_frame(|frame| {
+        frame.fg = (fg.red, fg.green, fg.blue, fg.alpha);
+        frame.bg = (bg.red, bg.green, bg.blue, bg.alpha);
+    })
+}
+
+fn to_color((red, green, blue, alpha): Rgba) -> Color {
+    Color {
+        red,
+        green,
+        blue,
+        alpha,
+    }
+}
@@ -0,0 +1,30 @@
+//`Platform` stores named blobs of bytes for the game, like settings and
+//progress, so it never has to touch the filesystem itself. Where they end up
+//is up to the backend: the windowed game keeps each in a file, and the
+//recording backend keeps them in memory.
+//
+//Names are checked the same way everywhere, so a name that works headless
+//works in the window too. Since the windowed game uses them as file names,
+//they are kept to ASCII letters, digits, `-`, `_` and `.`, and can't start
+//with a `.`.
+
+/// The longest a blob's name can be.
+pub const MAX_NAME_LENGTH: usize = 64;
+
+/// An error saying what is wrong with `name`, if it can't be used as the name
+/// of a blob.
+pub fn check_name(name: &str) -> Result<(), String> {
+    let allowed = |c: char| c.is_ascii_alphanumeric() || c == '-' || c == '_' || c == '.';
+
+    if name.is_empty() || name.len() > MAX_NAME_LENGTH {
+        Err(format!("\"{}\" should be between 1 and {} characters long",
+                    name,
+                    MAX_NAME_LENGTH))
+    } else if name.starts_with('.') || !name.chars().all(allowed) {
+        Err(format!("\"{}\" should only have letters, digits, '-', '_' and '.' in it, and \
+                     not start with '.'",
+                    name))
+    } else {
+        Ok(())
+    }
+}
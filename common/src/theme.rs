@@ -0,0 +1,298 @@
+//Every colour the game and the host draw with comes from a `Theme`, which
+//players can change in a file. The file starts from one of the built in
+//palettes, then has one line per colour to change, and can ask for boxes and
+//lines to be drawn with plain ASCII, for fonts and terminals without the box
+//drawing characters:
+//
+//    # comments start with a hash
+//    palette = colorblind
+//    boxes = ascii
+//    jump = #FF8000
+//
+//The palettes are `default`, `high-contrast`, and `colorblind`, which only
+//uses colours that can still be told apart with the common kinds of colour
+//blindness. Colours are written `#RRGGBB`. `Display` writes every setting out
+//in this format, which is also how the theme is stored in saved games.
+
+use std::collections::HashMap;
+use std::fmt;
+
+use {Color, OpcodeFamily, Token};
+
+pub const PALETTES: [&str; 3] = ["default", "high-contrast", "colorblind"];
+
+//declares `Theme` with a colour field for each name, so the names the file
+//uses can't drift away from the fields.
+macro_rules! theme {
+    ($($(#[$doc:meta])* $field:ident: $name:expr,)*) => {
+        #[derive(Clone, Copy, Debug, PartialEq)]
+        pub struct Theme {
+            $($(#[$doc])* pub $field: Color,)*
+            /// Whether to draw boxes and lines with ASCII rather than the box
+            /// drawing characters.
+            pub ascii_boxes: bool,
+        }
+
+        impl Theme {
+            fn color_mut(&mut self, name: &str) -> Option<&mut Color> {
+                match name {
+                    $($name => Some(&mut self.$field),)*
+                    _ => None,
+                }
+            }
+
+            fn colors(&self) -> Vec<(&'static str, Color)> {
+                vec![$(($name, self.$field)),*]
+            }
+        }
+    };
+}
+
+theme! {
+    text: "text",
+    background: "background",
+    /// What is being pointed at or executed, and whatever has the keyboard.
+    highlight_text: "highlight-text",
+    highlight_background: "highlight-background",
+    /// The address in front of each instruction on the playfield.
+    address: "address",
+    nop: "nop",
+    load: "load",
+    arithmetic: "arithmetic",
+    jump: "jump",
+    immediate: "immediate",
+    jump_target: "jump-target",
+    register: "register",
+    /// The arrows beside the playfield from jumps to where they go.
+    arrow: "arrow",
+    /// The mark beside a jump that would leave the playfield.
+    arrow_exit: "arrow-exit",
+    /// The heatmap beside the playfield goes from `heat-cold`, for an address
+    /// executed once, to `heat-hot`, for the one executed most.
+    heat_cold: "heat-cold",
+    heat_hot: "heat-hot",
+    /// The address of an instruction that has since been written over, while
+    /// peeling back the playfield's layers.
+    peeled: "peeled",
+    /// The outline beside every address the card under the mouse wrote to.
+    footprint: "footprint",
+    /// Instructions execution can never reach, see the `analysis` module.
+    dead: "dead",
+    status_text: "status-text",
+    status_success: "status-success",
+    /// The status line after something failed, and the box shown after the
+    /// game panics.
+    status_failure: "status-failure",
+}
+
+impl Default for Theme {
+    fn default() -> Self {
+        Theme {
+            text: Color::rgb(255, 255, 255),
+            background: Color::rgb(0, 0, 0),
+            highlight_text: Color::rgb(0, 0, 0),
+            highlight_background: Color::rgb(255, 255, 255),
+            address: Color::rgb(128, 128, 128),
+            nop: Color::rgb(128, 128, 128),
+            load: Color::rgb(96, 160, 255),
+            arithmetic: Color::rgb(96, 208, 96),
+            jump: Color::rgb(224, 96, 224),
+            immediate: Color::rgb(240, 200, 80),
+            jump_target: Color::rgb(255, 128, 64),
+            register: Color::rgb(96, 224, 224),
+            arrow: Color::rgb(160, 160, 160),
+            arrow_exit: Color::rgb(255, 64, 64),
+            heat_cold: Color::rgb(32, 32, 160),
+            heat_hot: Color::rgb(255, 64, 0),
+            peeled: Color::rgb(200, 160, 96),
+            footprint: Color::rgb(255, 255, 128),
+            dead: Color::rgb(80, 80, 80),
+            status_text: Color::rgb(255, 255, 255),
+            status_success: Color::rgb(0, 96, 0),
+            status_failure: Color::rgb(160, 0, 0),
+            ascii_boxes: false,
+        }
+    }
+}
+
+impl Theme {
+    /// The built in palette called `name`, one of `PALETTES`.
+    pub fn palette(name: &str) -> Option<Theme> {
+        match name {
+            "default" => Some(Theme::default()),
+            //only the brightest colours, on black.
+            "high-contrast" => {
+                Some(Theme {
+                    text: Color::rgb(255, 255, 255),
+                    background: Color::rgb(0, 0, 0),
+                    highlight_text: Color::rgb(0, 0, 0),
+                    highlight_background: Color::rgb(255, 255, 0),
+                    address: Color::rgb(255, 255, 255),
+                    nop: Color::rgb(255, 255, 255),
+                    load: Color::rgb(0, 255, 255),
+                    arithmetic: Color::rgb(0, 255, 0),
+                    jump: Color::rgb(255, 0, 255),
+                    immediate: Color::rgb(255, 255, 0),
+                    jump_target: Color::rgb(255, 255, 0),
+                    register: Color::rgb(0, 255, 255),
+                    arrow: Color::rgb(255, 255, 255),
+                    arrow_exit: Color::rgb(255, 0, 0),
+                    heat_cold: Color::rgb(64, 64, 64),
+                    heat_hot: Color::rgb(255, 255, 255),
+                    peeled: Color::rgb(255, 128, 0),
+                    footprint: Color::rgb(255, 255, 0),
+                    dead: Color::rgb(128, 128, 128),
+                    status_text: Color::rgb(0, 0, 0),
+                    status_success: Color::rgb(0, 255, 0),
+                    status_failure: Color::rgb(255, 255, 0),
+                    ..Theme::default()
+                })
+            }
+            //the Okabe-Ito colours.
+            "colorblind" => {
+                Some(Theme {
+                    address: Color::rgb(153, 153, 153),
+                    nop: Color::rgb(153, 153, 153),
+                    load: Color::rgb(86, 180, 233),
+                    arithmetic: Color::rgb(0, 158, 115),
+                    jump: Color::rgb(230, 159, 0),
+                    immediate: Color::rgb(240, 228, 66),
+                    jump_target: Color::rgb(213, 94, 0),
+                    register: Color::rgb(204, 121, 167),
+                    arrow: Color::rgb(153, 153, 153),
+                    arrow_exit: Color::rgb(213, 94, 0),
+                    heat_cold: Color::rgb(0, 114, 178),
+                    heat_hot: Color::rgb(240, 228, 66),
+                    peeled: Color::rgb(230, 159, 0),
+                    footprint: Color::rgb(240, 228, 66),
+                    dead: Color::rgb(96, 96, 96),
+                    status_success: Color::rgb(0, 114, 178),
+                    status_failure: Color::rgb(213, 94, 0),
+                    ..Theme::default()
+                })
+            }
+            _ => None,
+        }
+    }
+
+    /// The colour to draw `token` in.
+    pub fn token_color(&self, token: Token) -> Color {
+        match token {
+            Token::Opcode(OpcodeFamily::Nop) => self.nop,
+            Token::Opcode(OpcodeFamily::Load) => self.load,
+            Token::Opcode(OpcodeFamily::Arithmetic) => self.arithmetic,
+            Token::Opcode(OpcodeFamily::Jump) => self.jump,
+            Token::Immediate => self.immediate,
+            Token::JumpTarget => self.jump_target,
+            Token::Register => self.register,
+            Token::Space => self.text,
+        }
+    }
+
+    /// `text`, with any box drawing characters in it swapped for ASCII when
+    /// using ASCII boxes.
+    pub fn glyphs(&self, text: &str) -> String {
+        if self.ascii_boxes {
+            text.chars().map(ascii).collect()
+        } else {
+            text.to_string()
+        }
+    }
+
+    /// Reads a theme in the format described at the top of this module.
+    /// Lines that can't be used are skipped rather than failing the whole
+    /// file, and each is described in the returned problems.
+    pub fn parse(text: &str) -> (Theme, Vec<String>) {
+        let mut problems = Vec::new();
+        let mut settings = Vec::new();
+
+        for (line_index, line) in text.lines().enumerate() {
+            let line_number = line_index + 1;
+
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+
+            match line.find('=') {
+                Some(index) => {
+                    settings.push((line_number, line[..index].trim(), line[index + 1..].trim()))
+                }
+                None => problems.push(format!("line {}: expected \"NAME = VALUE\"", line_number)),
+            }
+        }
+
+        //the palette is what everything else changes, wherever it is in the file.
+        let mut theme = settings.iter()
+            .find(|&&(_, name, _)| name == "palette")
+            .and_then(|&(_, _, value)| Theme::palette(value))
+            .unwrap_or_default();
+        let mut first_lines: HashMap<&str, usize> = HashMap::new();
+
+        for &(line_number, name, value) in settings.iter() {
+            let mut problem =
+                |message: String| problems.push(format!("line {}: {}", line_number, message));
+
+            if let Some(&first_line) = first_lines.get(name) {
+                problem(format!("{} was already set on line {}", name, first_line));
+                continue;
+            }
+            first_lines.insert(name, line_number);
+
+            match name {
+                "palette" => {
+                    if Theme::palette(value).is_none() {
+                        problem(format!("\"{}\" is not a palette, expected one of {}",
+                                        value,
+                                        PALETTES.join(", ")))
+                    }
+                }
+                "boxes" => {
+                    match value {
+                        "unicode" => theme.ascii_boxes = false,
+                        "ascii" => theme.ascii_boxes = true,
+                        _ => problem(format!("\"{}\" should be unicode or ascii", value)),
+                    }
+                }
+                _ => {
+                    match theme.color_mut(name) {
+                        Some(color) => {
+                            match value.parse() {
+                                Ok(value) => *color = value,
+                                Err(error) => problem(error),
+                            }
+                        }
+                        None => problem(format!("\"{}\" is not a colour or setting", name)),
+                    }
+                }
+            }
+        }
+
+        (theme, problems)
+    }
+}
+
+impl fmt::Display for Theme {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        writeln!(f, "boxes = {}", if self.ascii_boxes { "ascii" } else { "unicode" })?;
+        for (name, color) in self.colors() {
+            writeln!(f, "{} = {}", name, color)?;
+        }
+
+        Ok(())
+    }
+}
+
+fn ascii(glyph: char) -> char {
+    match glyph {
+        '┌' | '┐' | '└' | '┘' | '╔' | '╗' | '╚' | '╝' | '╕' | '╖' | '╘' | '╙' | '├' | '┤' |
+        '┬' | '┴' | '┼' => '+',
+        '─' => '-',
+        '═' => '=',
+        '│' | '║' => '|',
+        '↑' => '^',
+        '↓' => 'v',
+        '×' => 'x',
+        _ => glyph,
+    }
+}
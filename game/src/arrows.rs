@@ -0,0 +1,171 @@
+use common::*;
+use common::Data::*;
+use common::Instruction::*;
+
+use {scroll_offset, shown_instruction};
+
+//Jumps only say where they go as a number, so the gutter left of the playfield
+//has an arrow from each jump to its target:
+//
+//    ┌──>0x02│load 0x02 A
+//    │   0x03│NOP
+//    └───0x04│JNZ  0x02 A
+//
+//Each arrow runs down its own column of the gutter, a lane, with the shortest
+//arrows nearest the playfield so they cross each other as little as possible.
+//Register jumps point wherever the register currently does, so their arrows
+//move as the program runs. A jump that would leave the playfield gets a `×`
+//instead of an arrow, and one that doesn't fit in a lane gets a `↑` or `↓`
+//saying which way it goes. While the playfield's layers are peeled back, the
+//arrows are for the instructions being shown.
+
+//which neighbours a piece of an arrow joins up with.
+const UP: u8 = 1;
+const DOWN: u8 = 2;
+const LEFT: u8 = 4;
+const RIGHT: u8 = 8;
+
+/// Where the instruction shown at `address` would jump to, if it is a jump.
+pub fn jump_target(game: &Game, address: usize) -> Option<usize> {
+    match shown_instruction(game, address) {
+        JumpZero(Immeadiate(target), _) |
+        JumpNotZero(Immeadiate(target), _) => Some(target as usize),
+        JumpRZero(register, _) |
+        JumpRNotZero(register, _) => Some(game.registers[register as usize] as usize),
+        _ => None,
+    }
+}
+
+pub fn draw(platform: &Platform, game: &Game) {
+    let gutter = game.layout.gutter;
+    if gutter.size.width <= 0 {
+        return;
+    }
+
+    let lane_count = (gutter.size.width - 1) as usize;
+    let height = gutter.size.height.max(0) as usize;
+    let first_address = scroll_offset(game);
+
+    //the row of the gutter `address` is on, if it is on screen.
+    let row = |address: usize| {
+        let row = address as i32 - first_address;
+        if row >= 0 && (row as usize) < height {
+            Some(row as usize)
+        } else {
+            None
+        }
+    };
+
+    //the connections in each lane on each row, and what goes in the column
+    //beside the playfield.
+    let mut lines = vec![vec![0u8; lane_count]; height];
+    let mut tails: Vec<Option<&str>> = vec![None; height];
+    let mut heads: Vec<Option<&str>> = vec![None; height];
+    let mut exits: Vec<bool> = vec![false; height];
+
+    let mut jumps: Vec<(usize, usize)> = (0..PLAYFIELD_SIZE)
+        .filter_map(|address| jump_target(game, address).map(|target| (address, target)))
+        .collect();
+    jumps.sort_by_key(|&(from, to)| (from.max(to) - from.min(to), from));
+
+    //the addresses each lane is already used between.
+    let mut lanes: Vec<Vec<(usize, usize)>> = vec![Vec::new(); lane_count];
+
+    for (from, to) in jumps {
+        if to >= PLAYFIELD_SIZE {
+            if let Some(row) = row(from) {
+                exits[row] = true;
+            }
+            continue;
+        }
+
+        let (top, bottom) = (from.min(to), from.max(to));
+        let lane = lanes.iter().position(|spans| {
+            spans.iter().all(|&(span_top, span_bottom)| bottom < span_top || top > span_bottom)
+        });
+
+        let lane = match lane {
+            Some(lane) => lane,
+            None => {
+                if let Some(row) = row(from) {
+                    tails[row] = Some(if to < from { "↑" } else { "↓" });
+                }
+                continue;
+            }
+        };
+        lanes[lane].push((top, bottom));
+
+        //lane 0 is the one nearest the playfield.
+        let column = lane_count - 1 - lane;
+        for address in top..bottom + 1 {
+            let row = match row(address) {
+                Some(row) => row,
+                None => continue,
+            };
+
+            let mut connections = 0;
+            if address > top {
+                connections |= UP;
+            }
+            if address < bottom {
+                connections |= DOWN;
+            }
+            if address == top || address == bottom {
+                connections |= RIGHT;
+                for line in lines[row][column + 1..].iter_mut() {
+                    *line |= LEFT | RIGHT;
+                }
+            }
+            lines[row][column] |= connections;
+        }
+
+        if let Some(row) = row(from) {
+            tails[row] = tails[row].or(Some("─"));
+        }
+        if let Some(row) = row(to) {
+            heads[row] = Some(">");
+        }
+    }
+
+    let theme = &game.theme;
+    let left = gutter.top_left.x;
+    let head_x = gutter.top_right.x - 1;
+
+    for (row, connections) in lines.iter().enumerate() {
+        let y = gutter.top_left.y + row as i32;
+        let text: String = connections.iter().map(|&connections| line(connections)).collect();
+
+        (platform.set_colors)(theme.arrow, theme.background);
+        (platform.print_xy)(left, y, &theme.glyphs(&text));
+
+        if exits[row] {
+            (platform.set_colors)(theme.arrow_exit, theme.background);
+            (platform.print_xy)(head_x, y, &theme.glyphs("×"));
+        } else if let Some(head) = heads[row].or(tails[row]) {
+            (platform.print_xy)(head_x, y, &theme.glyphs(head));
+        }
+    }
+
+    (platform.set_colors)(theme.text, theme.background);
+}
+
+//the box drawing character joining up `connections`.
+fn line(connections: u8) -> char {
+    let joins = |direction: u8| connections & direction != 0;
+
+    match (joins(UP), joins(DOWN), joins(LEFT), joins(RIGHT)) {
+        (false, false, false, false) => ' ',
+        //including a jump to itself, which only goes right.
+        (false, false, _, _) => '─',
+        (_, _, false, false) => '│',
+        (false, true, false, true) => '┌',
+        (true, false, false, true) => '└',
+        (false, true, true, false) => '┐',
+        (true, false, true, false) => '┘',
+        (true, true, false, true) => '├',
+        (true, true, true, false) => '┤',
+        (false, true, true, true) => '┬',
+        (true, false, true, true) => '┴',
+        (true, true, true, true) => '┼',
+    }
+}
@@ -0,0 +1,249 @@
+use common::*;
+use common::bindings::{Action, Mode};
+use common::Data::*;
+
+use {draw_address, draw_tokens, layout, scroll_offset};
+
+//The editor lets the playfield be changed directly instead of only by placing
+//cards, which makes setting up a particular scenario much quicker.
+//
+//The `ToggleEditor` action, Ctrl+E by default, toggles editor mode. While it
+//is on, clicking an address opens an inline editor for it, and shift-clicking
+//extends a selection from there.
+//While an address is open, the editor's actions come before the game's. By
+//default:
+//  Left/Right          choose the opcode, data or register field
+//  Up/Down             change the chosen field (+Shift to move data by 0x10)
+//  Delete/Backspace    replace the selected instructions with NOPs
+//  Ctrl+C / Ctrl+V     copy the selection / paste at the open address
+//  Enter/Escape        close the editor
+
+//returns true if the event was used by the editor
+pub fn handle_event(platform: &Platform, game: &mut Game, event: &Event) -> bool {
+    if game.bindings.action_for_event(Mode::Game, event) == Some(Action::ToggleEditor) {
+        game.editor.enabled = !game.editor.enabled;
+        close(&mut game.editor);
+
+        return true;
+    }
+
+    if !game.editor.enabled {
+        return false;
+    }
+
+    match *event {
+        Event::KeyPressed { key: KeyCode::MouseLeft, ctrl: _, shift } => {
+            if game.selected_card.is_some() {
+                return false;
+            }
+
+            let mouse_pos = (platform.mouse_position)();
+            if let Some(address) = address_at(game, mouse_pos) {
+                if shift && game.editor.address.is_some() {
+                    game.editor.selection_end = Some(address);
+                } else {
+                    game.editor.address = Some(address);
+                    game.editor.selection_end = None;
+                    game.editor.field = 0;
+                }
+
+                return true;
+            }
+
+            false
+        }
+        _ => {
+            let address = match game.editor.address {
+                Some(address) => address,
+                None => return false,
+            };
+            let action = match game.bindings.action_for_event(Mode::Editor, event) {
+                Some(action) => action,
+                None => return false,
+            };
+
+            match action {
+                Action::EditorPreviousField => {
+                    let field_count = field_count(game.instructions[address]);
+                    game.editor.field = (game.editor.field + field_count - 1) % field_count;
+                }
+                Action::EditorNextField => {
+                    let field_count = field_count(game.instructions[address]);
+                    game.editor.field = (game.editor.field + 1) % field_count;
+                }
+                Action::EditorIncrease => adjust_field(game, address, 1),
+                Action::EditorDecrease => adjust_field(game, address, -1),
+                Action::EditorIncreaseFast => adjust_field(game, address, 0x10),
+                Action::EditorDecreaseFast => adjust_field(game, address, -0x10),
+                Action::EditorClear => {
+                    if let Some((start, end)) = game.editor.selection() {
+                        for i in start..end + 1 {
+                            set_instruction(game, i, Instruction::NOP);
+                        }
+                    }
+                    game.editor.field = 0;
+                }
+                Action::EditorCopy => {
+                    if let Some((start, end)) = game.editor.selection() {
+                        game.editor.clipboard = game.instructions[start..end + 1].to_vec();
+                    }
+                }
+                Action::EditorPaste => paste(game, address),
+                Action::EditorClose => close(&mut game.editor),
+                _ => return false,
+            }
+
+            true
+        }
+    }
+}
+
+fn close(editor: &mut Editor) {
+    editor.address = None;
+    editor.selection_end = None;
+    editor.field = 0;
+}
+
+pub fn address_at(game: &Game, mouse_pos: Point) -> Option<usize> {
+    let playfield = game.layout.playfield;
+    if !layout::contains(playfield, mouse_pos) {
+        return None;
+    }
+
+    let address = scroll_offset(game) + mouse_pos.y - playfield.top_left.y;
+
+    if address >= 0 && address < PLAYFIELD_SIZE as i32 {
+        Some(address as usize)
+    } else {
+        None
+    }
+}
+
+fn field_count(instruction: Instruction) -> usize {
+    instruction.fields().len()
+}
+
+fn adjust_field(game: &mut Game, address: usize, delta: i32) {
+    let instruction = game.instructions[address];
+    let (opcode, data, register1, register2) = instruction.decompose();
+
+    let new_instruction = match (game.editor.field, instruction) {
+        (0, _) => {
+            let count = INSTRUCTION_VARIATION_COUNT as i32;
+            let new_opcode = ((opcode as i32 + delta.signum()) % count + count) % count;
+
+            Instruction::compose(new_opcode as u8, data, register1, register2)
+        }
+        (1, Instruction::JumpRZero(_, _)) |
+        (1, Instruction::JumpRNotZero(_, _)) => {
+            Instruction::compose(opcode, data, register1.offset(delta.signum()), register2)
+        }
+        (1, _) => {
+            let Immeadiate(value) = data;
+            let new_data = Immeadiate(value.wrapping_add(delta as u8));
+
+            Instruction::compose(opcode, new_data, register1, register2)
+        }
+        (2, Instruction::JumpRZero(_, _)) |
+        (2, Instruction::JumpRNotZero(_, _)) => {
+            Instruction::compose(opcode, data, register1, register2.offset(delta.signum()))
+        }
+        (2, _) => Instruction::compose(opcode, data, register1.offset(delta.signum()), register2),
+        _ => instruction,
+    };
+
+    set_instruction(game, address, new_instruction);
+
+    let field_count = field_count(new_instruction);
+    if game.editor.field >= field_count {
+        game.editor.field = field_count - 1;
+    }
+}
+
+fn paste(game: &mut Game, address: usize) {
+    let clipboard = game.editor.clipboard.clone();
+
+    for (i, instruction) in clipboard.into_iter().enumerate() {
+        if address + i < PLAYFIELD_SIZE {
+            set_instruction(game, address + i, instruction);
+        }
+    }
+}
+
+//the editor changes the top layer in place rather than adding one, since it is
+//for setting the playfield up, not playing on it.
+fn set_instruction(game: &mut Game, address: usize, instruction: Instruction) {
+    game.instructions[address] = instruction;
+    game.history.overwrite(address, instruction);
+}
+
+pub fn draw_instruction(platform: &Platform, game: &Game, y: i32, address: usize) {
+    let instruction = game.instructions[address];
+    let theme = &game.theme;
+
+    let selected = match game.editor.selection() {
+        Some((start, end)) => start <= address && address <= end,
+        None => false,
+    };
+
+    let highlight = selected || Some(address as i32) == game.executing_address;
+    let left = game.layout.playfield.top_left.x;
+    let mut x = left + draw_address(platform, theme, left, y, address, highlight);
+
+    if game.editor.address != Some(address) {
+        draw_tokens(platform, theme, x, y, usize::MAX, &instruction);
+        return;
+    }
+
+    //the tokens other than spaces are the fields, in the same order.
+    let fields = instruction.tokens().into_iter().filter(|&(token, _)| token != Token::Space);
+    for (i, (token, field)) in fields.enumerate() {
+        if i == game.editor.field {
+            (platform.set_colors)(theme.highlight_text, theme.highlight_background);
+        } else {
+            (platform.set_colors)(theme.token_color(token), theme.background);
+        }
+        (platform.print_xy)(x, y, &field);
+
+        x += field.chars().count() as i32 + 1;
+    }
+    (platform.set_colors)(theme.text, theme.background);
+}
+
+#[cfg(test)]
+mod tests {
+    use common::*;
+    use common::bindings::Bindings;
+
+    use super::handle_event;
+
+    fn key(key: KeyCode) -> Event {
+        Event::KeyPressed {
+            key,
+            ctrl: false,
+            shift: false,
+        }
+    }
+
+    #[test]
+    fn editor_keys_can_be_rebound() {
+        let platform = recording::platform();
+        let mut game = ::new_game([Instruction::NOP; PLAYFIELD_SIZE],
+                                  Size::new(80, 30),
+                                  DEFAULT_SEED,
+                                  DEFAULT_SEED);
+        game.bindings = Bindings::parse("EditorIncrease = W\nEditorClose = Q\n").0;
+        game.editor.enabled = true;
+        game.editor.address = Some(2);
+
+        //Up is no longer the editor's, so it is left for the game.
+        assert!(!handle_event(&platform, &mut game, &key(KeyCode::Up)));
+        assert!(game.instructions[2] == Instruction::NOP);
+
+        assert!(handle_event(&platform, &mut game, &key(KeyCode::W)));
+        assert!(game.instructions[2] != Instruction::NOP);
+
+        assert!(handle_event(&platform, &mut game, &key(KeyCode::Q)));
+        assert_eq!(game.editor.address, None);
+    }
+}
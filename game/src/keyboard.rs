@@ -0,0 +1,149 @@
+use common::*;
+use common::bindings::Action;
+
+use {run, step_and_pause, stop, toggle_pause};
+
+//Everything that can be done with the mouse can also be done with an `Action`,
+//and those are bound to keys by `game.bindings`. See `common::bindings` for
+//the default keys and how to change them.
+//
+//A card selected with `NextCard`, `SelectCard1` and so on is shown at
+//`game.placement_cursor` instead of following the mouse, until the mouse
+//moves, and the scroll actions move it rather than the playfield.
+
+//the blob `ExportProfile` writes the profile report to.
+const PROFILE_BLOB: &str = "profile.txt";
+
+//returns true if quit requested
+pub fn handle_action(platform: &Platform, game: &mut Game, action: Action) -> bool {
+    if let Some(index) = action.card_index() {
+        if index < game.cards.len() {
+            select(game, index);
+        }
+
+        return false;
+    }
+
+    match action {
+        Action::Quit => {
+            if game.selected_card.is_none() {
+                return true;
+            }
+
+            deselect(game);
+        }
+        Action::Reset => game.new_game_screen = Some(NewGameScreen::default()),
+        Action::ScrollUp => scroll(game, -1),
+        Action::ScrollDown => scroll(game, 1),
+        Action::ScrollUpFast => scroll(game, -0x10),
+        Action::ScrollDownFast => scroll(game, 0x10),
+        Action::Run => run(game),
+        Action::Pause => toggle_pause(game),
+        Action::Step => step_and_pause(game),
+        Action::Break => stop(game),
+        Action::NextCard => cycle(game, true),
+        Action::PreviousCard => cycle(game, false),
+        Action::Place => {
+            if let (Some(index), Some(address)) = (game.selected_card, game.placement_cursor) {
+                ::place_card(game, index, address, (platform.time)());
+                deselect(game);
+            }
+        }
+        Action::Deselect => deselect(game),
+        //the editor sees every event first, so it has already handled this.
+        Action::ToggleEditor => {}
+        Action::ExportProfile => export_profile(platform, game),
+        Action::PeelLayer => ::peel(game, 1),
+        Action::UnpeelLayer => ::peel(game, -1),
+        Action::Scrape => {
+            let mouse_address = ::editor::address_at(game, (platform.mouse_position)());
+            if let Some(address) = game.placement_cursor.or(mouse_address) {
+                ::scrape(game, address);
+            }
+        }
+        Action::SelectCard1 | Action::SelectCard2 | Action::SelectCard3 | Action::SelectCard4 |
+        Action::SelectCard5 | Action::SelectCard6 | Action::SelectCard7 | Action::SelectCard8 |
+        Action::SelectCard9 => {}
+        //only looked up in the editor's mode, by the editor.
+        Action::EditorPreviousField | Action::EditorNextField | Action::EditorIncrease |
+        Action::EditorDecrease | Action::EditorIncreaseFast | Action::EditorDecreaseFast |
+        Action::EditorClear | Action::EditorCopy | Action::EditorPaste | Action::EditorClose => {}
+    }
+
+    false
+}
+
+fn export_profile(platform: &Platform, game: &Game) {
+    let report = game.profile.report(&game.instructions);
+
+    match (platform.write_blob)(PROFILE_BLOB, report.as_bytes()) {
+        Ok(()) => (platform.log)(&format!("wrote the profile to {}", PROFILE_BLOB)),
+        Err(error) => (platform.log)(&format!("couldn't write the profile: {}", error)),
+    }
+}
+
+//moves the card being placed with the keyboard if there is one, otherwise
+//scrolls the playfield.
+fn scroll(game: &mut Game, delta: i32) {
+    if game.selected_card.is_some() && game.placement_cursor.is_some() {
+        move_cursor(game, delta);
+    } else {
+        game.scroll_offset = game.scroll_offset.saturating_add(delta);
+    }
+}
+
+fn cycle(game: &mut Game, forwards: bool) {
+    let count = game.cards.len();
+    if count == 0 {
+        return;
+    }
+
+    let index = match game.selected_card {
+        Some(index) if forwards => (index + 1) % count,
+        Some(index) => (index + count - 1) % count,
+        None if forwards => 0,
+        None => count - 1,
+    };
+    select(game, index);
+}
+
+fn select(game: &mut Game, index: usize) {
+    game.selected_card = Some(index);
+
+    //keep the cursor where it is when going from one card to the next, and
+    //otherwise start at the top of the screen.
+    let start = game.placement_cursor.unwrap_or_else(|| ::scroll_offset(game).max(0) as usize);
+    game.placement_cursor = Some(start);
+
+    move_cursor(game, 0);
+}
+
+fn deselect(game: &mut Game) {
+    game.selected_card = None;
+    game.placement_cursor = None;
+}
+
+//moves the cursor by `delta` addresses, scrolling to keep it on screen.
+fn move_cursor(game: &mut Game, delta: i32) {
+    let address = match game.placement_cursor {
+        Some(address) => address as i32,
+        None => return,
+    };
+    let address = (address + delta).max(0).min(PLAYFIELD_SIZE as i32 - 1);
+    game.placement_cursor = Some(address as usize);
+
+    //the card's first instruction is one row below its top edge, and the
+    //whole card should fit inside the playfield panel.
+    let height = game.layout.playfield.size.height;
+    let first_row = 1;
+    let last_row = (height - ::CARD_HEIGHT + 1).max(first_row);
+
+    let scroll_offset = ::scroll_offset(game);
+    game.scroll_offset = if address - scroll_offset < first_row {
+        address - first_row
+    } else if address - scroll_offset > last_row {
+        address - last_row
+    } else {
+        scroll_offset
+    };
+}
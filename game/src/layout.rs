@@ -0,0 +1,145 @@
+use common::*;
+
+use {CARD_WIDTH, GENERAL_REGISTER_DISPLAY_HEIGHT, REGISTERS_X_OFFSET};
+
+//Everything on screen is placed inside one of the panels of a `Layout`, which
+//is worked out from the window size here instead of from fixed offsets, so
+//resizing the window only means working it out again. At 80x30 it comes out
+//as:
+//
+//    +-----+----------------+--+-----------------------+--------------------------------+
+//    |gut- |playfield       |  |trace                  |registers                       |
+//    |ter  |16 wide         |  |                       +--------------------+-----------+
+//    |     |                |  |                       |                    |controls   |
+//    |     |                |  |                       |                    |           |
+//    +-----+----------------+--+-----------------------+--------------------+-----------+
+//    |hand, the bottom 8 rows                                                           |
+//    +----------------------------------------------------------------------------------+
+//
+//The first column of the gap right of the playfield is the heatmap, showing how
+//often each address has been executed.
+//
+//Anything that doesn't fit in a small window is squeezed down to nothing
+//rather than overlapping its neighbours.
+
+pub const PLAYFIELD_WIDTH: i32 = 16;
+//room for four arrows side by side, and their heads.
+pub const GUTTER_WIDTH: i32 = 5;
+//how much of each card shows above the bottom of the window.
+pub const HAND_HEIGHT: i32 = 8;
+
+const PANEL_GAP: i32 = 2;
+
+const REGISTERS_HEIGHT: i32 = GENERAL_REGISTER_DISPLAY_HEIGHT + 1;
+
+pub const BUTTON_WIDTH: i32 = 11;
+pub const BUTTON_HEIGHT: i32 = 3;
+const BUTTON_GAP: i32 = 1;
+const BUTTON_COUNT: i32 = 3;
+
+//how far apart the cards in the hand are when there is room, and how close
+//together they are allowed to get when there isn't.
+const CARD_SPACING: i32 = 12;
+const MIN_CARD_SPACING: i32 = 1;
+const HAND_MARGIN: i32 = 1;
+
+pub fn compute(size: Size) -> Layout {
+    let width = size.width.max(0);
+    let height = size.height.max(0);
+
+    let hand_height = HAND_HEIGHT.min(height);
+    let top_height = height - hand_height;
+
+    //the playfield matters more than the arrows beside it.
+    let playfield_width = PLAYFIELD_WIDTH.min(width);
+    let gutter_width = GUTTER_WIDTH.min(width - playfield_width);
+    let left_width = gutter_width + playfield_width;
+    let registers_width = REGISTERS_X_OFFSET.min(width - left_width - PANEL_GAP).max(0);
+    let registers_x = width - registers_width;
+
+    //the buttons keep a column free at the right edge of the window.
+    let controls_width = BUTTON_WIDTH.min(registers_width);
+    let controls_x = (width - controls_width - 1).max(registers_x);
+    let controls_y = (REGISTERS_HEIGHT + 1).min(top_height);
+    let controls_height = (BUTTON_COUNT * (BUTTON_HEIGHT + BUTTON_GAP) - BUTTON_GAP)
+        .min(top_height - controls_y);
+
+    let trace_x = (left_width + PANEL_GAP).min(registers_x);
+    let trace_width = (registers_x - PANEL_GAP - trace_x).max(0);
+
+    Layout {
+        size: Size::new(width, height),
+        gutter: Rect::from_values(0, 0, gutter_width, top_height),
+        playfield: Rect::from_values(gutter_width, 0, playfield_width, top_height),
+        heatmap: Rect::from_values(left_width, 0, 1.min(width - left_width), top_height),
+        trace: Rect::from_values(trace_x, 0, trace_width, top_height),
+        registers: Rect::from_values(registers_x,
+                                     0,
+                                     registers_width,
+                                     REGISTERS_HEIGHT.min(top_height)),
+        controls: Rect::from_values(controls_x, controls_y, controls_width, controls_height),
+        hand: Rect::from_values(0, top_height, width, hand_height),
+    }
+}
+
+/// The `index`th button down the controls panel, or `None` if the window is
+/// too small for it to fit.
+pub fn button(layout: &Layout, index: i32, text: &str) -> Option<ButtonSpec> {
+    let controls = layout.controls;
+    let y = controls.top_left.y + index * (BUTTON_HEIGHT + BUTTON_GAP);
+
+    if controls.size.width < BUTTON_WIDTH || y + BUTTON_HEIGHT > controls.bottom_left.y {
+        return None;
+    }
+
+    Some(ButtonSpec {
+        x: controls.top_left.x,
+        y,
+        w: controls.size.width,
+        h: BUTTON_HEIGHT,
+        text: text.to_string(),
+    })
+}
+
+/// Lines the cards up along the hand panel, fanning them out over each other
+/// when there isn't room for them side by side.
+pub fn arrange_hand(layout: &Layout, cards: &mut [Card]) {
+    let hand = layout.hand;
+    let count = cards.len() as i32;
+
+    let spacing = if count > 1 {
+        ((hand.size.width - 2 * HAND_MARGIN - CARD_WIDTH) / (count - 1))
+            .clamp(MIN_CARD_SPACING, CARD_SPACING)
+    } else {
+        CARD_SPACING
+    };
+
+    let mut x = hand.top_left.x + HAND_MARGIN;
+    for card in cards.iter_mut() {
+        card.location = Point::new(x, hand.top_left.y);
+        x += spacing;
+    }
+}
+
+/// Whether `point` is inside `rect`.
+pub fn contains(rect: Rect, point: Point) -> bool {
+    ::inside_rect(point,
+                  rect.top_left.x,
+                  rect.top_left.y,
+                  rect.size.width,
+                  rect.size.height)
+}
+
+
+/// Prints `text` at `x`, `y`, cutting off whatever falls outside `rect`.
+pub fn print_in(platform: &Platform, rect: Rect, x: i32, y: i32, text: &str) {
+    if y < rect.top_left.y || y >= rect.bottom_left.y || x >= rect.top_right.x {
+        return;
+    }
+
+    let skip = (rect.top_left.x - x).max(0) as usize;
+    let take = (rect.top_right.x - x).max(0) as usize;
+    let visible: String = text.chars().take(take).skip(skip).collect();
+
+    (platform.print_xy)(x.max(rect.top_left.x), y, &visible);
+}
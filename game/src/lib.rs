@@ -3,50 +3,175 @@ extern crate rand;
 
 use rand::{Rng, SeedableRng, StdRng};
 
+use std::mem;
+use std::time::Duration;
+
 use common::*;
+use common::abi::GameHandle;
+use common::history::{CardId, History, Layer, Placement};
+use common::theme::Theme;
 use common::Register::*;
 use common::Data::*;
 use common::Instruction::*;
 
+mod arrows;
+mod editor;
+mod keyboard;
+mod layout;
+mod new_game_screen;
+mod panics;
+mod provenance;
+mod ui;
+
+//the host looks these up by name with these types, so make sure they match.
+const _: abi::AbiVersion = abi_version;
+const _: abi::GameLayoutHash = game_layout_hash;
+const _: abi::CreateGame = create_game;
+const _: abi::UpdateGame = update_game;
+const _: abi::SaveGame = save_game;
+const _: abi::LoadGame = load_game;
+const _: abi::DropGame = drop_game;
+const _: abi::SetBindings = set_bindings;
+const _: abi::SetTheme = set_theme;
+const _: abi::InvalidateScreen = invalidate_screen;
+const _: abi::UpdateAndRender = update_and_render;
+
 #[no_mangle]
-pub fn new_game(instructions: [Instruction; PLAYFIELD_SIZE], size: Size) -> Game {
+pub fn abi_version() -> u32 {
+    abi::ABI_VERSION
+}
 
-    let seed: &[_] = &[42];
-    let mut rng: StdRng = SeedableRng::from_seed(seed);
+#[no_mangle]
+pub fn game_layout_hash() -> u64 {
+    abi::game_layout_hash()
+}
 
-    let cards = get_cards(&mut rng, size.height);
+#[no_mangle]
+pub fn create_game(instructions: [Instruction; PLAYFIELD_SIZE],
+                   size: Size,
+                   seed: Seed,
+                   daily_seed: Seed)
+                   -> GameHandle {
+    GameHandle::new(new_game(instructions, size, seed, daily_seed))
+}
 
-    let run_button_width = 11;
-    let run_button_spec = ButtonSpec {
-        x: size.width - (run_button_width + 1),
-        y: 4,
-        w: run_button_width,
-        h: 3,
-        text: "Run".to_string(),
-    };
+#[no_mangle]
+pub fn update_game(platform: &Platform,
+                   handle: &mut GameHandle,
+                   events: &mut Vec<Event>)
+                   -> Result<bool, String> {
+    let game = unsafe { handle.game_mut() };
+
+    //a panic can leave the game half updated, so it goes back to how it was
+    //before the frame. This happens every frame, so the copy is kept cheap:
+    //the history shares its layers with the copy until something changes
+    //them, and the last frame drawn is left out, which only means the whole
+    //screen is drawn again after a panic.
+    let screen = mem::take(&mut game.screen);
+    let before = game.clone();
+    game.screen = screen;
+
+    panics::catch(|| update_and_render(platform, game, events)).inspect_err(|message| {
+        (platform.log)(&format!("the game panicked: {}", message));
+
+        *game = before;
+    })
+}
+
+#[no_mangle]
+pub fn save_game(handle: &GameHandle) -> String {
+    save::save(unsafe { handle.game() })
+}
+
+#[no_mangle]
+pub fn load_game(text: &str, size: Size) -> Result<GameHandle, String> {
+    load(text, size).map(GameHandle::new)
+}
+
+#[no_mangle]
+pub fn drop_game(handle: GameHandle) {
+    drop(unsafe { handle.into_game() });
+}
+
+#[no_mangle]
+pub fn set_bindings(handle: &mut GameHandle, bindings: bindings::Bindings) {
+    unsafe { handle.game_mut() }.bindings = bindings;
+}
+
+#[no_mangle]
+pub fn set_theme(handle: &mut GameHandle, theme: theme::Theme) {
+    unsafe { handle.game_mut() }.theme = theme;
+}
+
+fn load(text: &str, size: Size) -> Result<Game, String> {
+    let defaults = new_game(common::get_instructions(), size, DEFAULT_SEED, DEFAULT_SEED);
+
+    save::load(text, defaults).map(|mut game| {
+        //the layout code may have changed since the cards were put where they are.
+        layout::arrange_hand(&game.layout, &mut game.cards);
+
+        game
+    })
+}
+
+#[no_mangle]
+pub fn invalidate_screen(handle: &mut GameHandle) {
+    unsafe { handle.game_mut() }.screen.invalidate();
+}
+
+#[no_mangle]
+pub fn new_game(instructions: [Instruction; PLAYFIELD_SIZE],
+                size: Size,
+                seed: Seed,
+                daily_seed: Seed)
+                -> Game {
+
+    let rng_seed: &[_] = &[seed as usize];
+    let mut rng: StdRng = SeedableRng::from_seed(rng_seed);
+
+    let layout = layout::compute(size);
+    let mut next_card_id = 1;
+    let cards = get_cards(&mut rng, &layout, &mut next_card_id);
 
     Game {
         instructions: instructions,
+        history: History::new(&instructions),
+        turn: 0,
+        player: 1,
+        peeled_layers: 0,
         scroll_offset: 0,
         cards: cards,
+        next_card_id,
         selected_card: None,
-        playfield_right_edge: 16,
+        layout,
         ui_context: UIContext {
             hot: 0,
             active: 0,
             next_hot: 0,
+            focused: 0,
         },
-        run_button_spec: run_button_spec,
         paused: false,
         executing_address: None,
         instruction_countdown: COUNTDOWN_LENGTH,
         registers: [0; REGISTER_AMOUNT],
+        trace: Vec::new(),
+        trace_list: ListState::default(),
+        profile: profile::Profile::default(),
         rng: rng,
+        editor: Editor::default(),
+        seed,
+        daily_seed,
+        new_game_screen: None,
+        placement_cursor: None,
+        bindings: bindings::Bindings::default(),
+        theme: theme::Theme::default(),
+        screen: screen::Screen::default(),
     }
 }
 
 
-fn get_cards(rng: &mut StdRng, height: i32) -> Vec<Card> {
+//deals a hand, numbering the cards from `next_card_id` on.
+fn get_cards(rng: &mut StdRng, layout: &Layout, next_card_id: &mut CardId) -> Vec<Card> {
 
     let mut instructions_vector = vec![];
 
@@ -63,106 +188,107 @@ fn get_cards(rng: &mut StdRng, height: i32) -> Vec<Card> {
         instructions_vector.push(instructions);
     }
 
-    make_hand(height, instructions_vector)
+    make_hand(layout, instructions_vector, next_card_id)
 }
 
+//how many updates each instruction takes to execute. There are
+//`clock::TICKS_PER_SECOND` updates a second.
 const COUNTDOWN_LENGTH: u16 = 60;
 const COUNTDOWN_NOP_LENGTH: u16 = 10;
 
-const CARD_OFFSET: i32 = 12;
-const CARD_OFFSET_DELTA: i32 = 12;
+//how many executed addresses `game.trace` keeps.
+const TRACE_LENGTH: usize = 64;
 
-fn make_hand(height: i32, instructions_list: Vec<Vec<Instruction>>) -> Vec<Card> {
-    let mut result = Vec::new();
+fn make_hand(layout: &Layout,
+             instructions_list: Vec<Vec<Instruction>>,
+             next_card_id: &mut CardId)
+             -> Vec<Card> {
+    let mut cards: Vec<Card> = instructions_list.into_iter()
+        .map(|instructions| {
+            let id = *next_card_id;
+            *next_card_id += 1;
 
-    let mut offset = CARD_OFFSET;
-    for instructions in instructions_list {
-        result.push(Card::new(offset, hand_height(height), instructions));
+            Card::new(id, 0, 0, instructions)
+        })
+        .collect();
 
-        offset += CARD_OFFSET_DELTA;
-    }
+    layout::arrange_hand(layout, &mut cards);
 
-    result
+    cards
 }
 
-pub fn hand_height(height: i32) -> i32 {
-    height - HAND_HEIGHT_OFFSET
-}
+#[no_mangle]
+//returns true if quit requested
+pub fn update_and_render(platform: &Platform, game: &mut Game, events: &mut Vec<Event>) -> bool {
+    //starting a new game replaces `game`, screen and all, so it is kept aside.
+    let mut screen = mem::take(&mut game.screen);
+    let quit = screen::render(platform, &mut screen, |platform| {
+        (platform.set_colors)(game.theme.text, game.theme.background);
+        (platform.clear)(None);
 
-const HAND_HEIGHT_OFFSET: i32 = 8;
+        update(platform, game, events)
+    });
+    game.screen = screen;
 
-fn collect_hand(cards: &mut Vec<Card>) {
-    let mut offset = CARD_OFFSET;
-    for card in cards.iter_mut() {
-        card.location.x = offset;
-        offset += CARD_OFFSET_DELTA;
-    }
+    quit
 }
 
-#[no_mangle]
 //returns true if quit requested
-pub fn update_and_render(platform: &Platform, game: &mut Game, events: &mut Vec<Event>) -> bool {
-    let mut left_mouse_pressed = false;
-    let mut left_mouse_released = false;
+fn update(platform: &Platform, game: &mut Game, events: &mut Vec<Event>) -> bool {
+    let layout = layout::compute((platform.size)());
+    if layout != game.layout {
+        game.layout = layout;
+        layout::arrange_hand(&game.layout, &mut game.cards);
+    }
+
+    if game.new_game_screen.is_some() {
+        return new_game_screen::update_and_render(platform, game, events);
+    }
+
+    let input = ui::Input::new(platform, events);
 
     for event in events {
+        if editor::handle_event(platform, game, event) {
+            continue;
+        }
+
+        if let Some(action) = game.bindings.action_for_event(bindings::Mode::Game, event) {
+            if keyboard::handle_action(platform, game, action) {
+                return true;
+            }
+
+            continue;
+        }
 
         match *event {
-            Event::MouseScroll { delta } => {
+            Event::MouseMove { x: _, y: _ } => {
+                game.placement_cursor = None;
+            }
+            //the trace list scrolls itself.
+            Event::MouseScroll { delta } if !layout::contains(game.layout.trace, input.mouse) => {
                 game.scroll_offset = game.scroll_offset.saturating_add(delta);
             }
             Event::KeyPressed { key: KeyCode::MouseLeft, ctrl: _, shift: _ } => {
-                left_mouse_pressed = true;
-
                 let mouse_pos = (platform.mouse_position)();
                 if let Some(index) = game.selected_card {
 
                     if let Some(address) = over_address(game, mouse_pos) {
-                        let instructions = game.cards.remove(index).instructions;
-
-                        let mut current_address = address;
-                        for i in 0..instructions.len() {
-                            game.instructions[current_address] = instructions[i];
-                            current_address += 1;
-                        }
-
-                        collect_hand(&mut game.cards);
+                        place_card(game, index, address, (platform.time)());
                     }
 
                     game.selected_card = None;
+                    game.placement_cursor = None;
                 } else {
                     game.selected_card = clicked_card(game, mouse_pos);
                 }
             }
-            Event::KeyReleased { key: KeyCode::MouseLeft, ctrl: _, shift: _ } => {
-                left_mouse_released = true;
-            }
-            Event::KeyPressed { key: KeyCode::MouseRight, ctrl: _, shift: _ } => {
-                game.selected_card = None;
-            }
-            Event::KeyPressed { key: KeyCode::Up, ctrl: _, shift: _ } => {
-                game.scroll_offset = game.scroll_offset.saturating_add(-1);
-            }
-            Event::KeyPressed { key: KeyCode::Down, ctrl: _, shift: _ } => {
-                game.scroll_offset = game.scroll_offset.saturating_add(1);
-            }
-            Event::KeyPressed { key: KeyCode::R, ctrl: true, shift: _ } => {
-                *game = new_game(common::get_instructions(), (platform.size)());
-            }
-            Event::Resize { width, height } => {
-                for card in game.cards.iter_mut() {
-                    card.location.y = hand_height(height);
-                }
-            }
-            Event::Close |
-            Event::KeyPressed { key: KeyCode::Escape, ctrl: _, shift: _ } => return true,
+            Event::Close => return true,
             _ => (),
         }
     }
 
     if game.cards.len() <= 0 {
-        let height = (platform.size)().height;
-        game.cards = get_cards(&mut game.rng, height);
+        deal(game);
     }
 
     if !game.paused {
@@ -170,13 +296,7 @@ pub fn update_and_render(platform: &Platform, game: &mut Game, events: &mut Vec<
             game.instruction_countdown -= 1;
 
             if game.instruction_countdown <= 0 {
-                let new_address = execute(game, address);
-
-                if is_on_playfield(new_address) {
-                    set_executing_address(game, new_address);
-                } else {
-                    game.executing_address = None;
-                }
+                step(game, address);
             }
 
         }
@@ -194,59 +314,119 @@ pub fn update_and_render(platform: &Platform, game: &mut Game, events: &mut Vec<
 
     game.ui_context.frame_init();
 
-    if do_button(platform,
-                 &mut game.ui_context,
-                 &game.run_button_spec,
-                 -704788405,
-                 left_mouse_pressed,
-                 left_mouse_released) {
-        set_executing_address(game, 0);
-    }
-
-    let pause_spec = ButtonSpec {
-        x: game.run_button_spec.x,
-        y: 8,
-        w: game.run_button_spec.w,
-        h: 3,
-        text: if game.paused {
-            "Resume".to_string()
-        } else {
-            "Pause".to_string()
-        },
-    };
+    do_controls(&ui::Ui::new(platform, &input, game.theme), game);
+
+    draw(platform, game);
 
-    if do_button(platform,
-                 &mut game.ui_context,
-                 &pause_spec,
-                 -804788405,
-                 left_mouse_pressed,
-                 left_mouse_released) {
-        game.paused = !game.paused;
-    }
-
-    let break_spec = ButtonSpec {
-        x: game.run_button_spec.x,
-        y: 12,
-        w: game.run_button_spec.w,
-        h: 3,
-        text: "Break".to_string(),
+    false
+}
+
+/// Removes the card at `index` from the hand and writes its instructions onto
+/// the playfield starting at `address`, as a new layer over what was there.
+/// Instructions that would land past the end of the playfield are dropped.
+/// `time` is the `Platform::time` it was placed at.
+pub fn place_card(game: &mut Game, index: usize, address: usize, time: Duration) {
+    let card = game.cards.remove(index);
+    game.turn += 1;
+
+    let placement = Placement {
+        card: card.id,
+        player: game.player,
+        turn: game.turn,
+        time,
     };
 
-    if do_button(platform,
-                 &mut game.ui_context,
-                 &break_spec,
-                 -904788405,
-                 left_mouse_pressed,
-                 left_mouse_released) {
+    for (i, instruction) in card.instructions.into_iter().enumerate() {
+        if let Some(cell) = game.instructions.get_mut(address + i) {
+            *cell = instruction;
+            game.history.write(address + i,
+                               Layer {
+                                   instruction,
+                                   placement: Some(placement),
+                               });
+        }
+    }
+
+    layout::arrange_hand(&game.layout, &mut game.cards);
+}
+
+/// Throws away the top layer at `address`, putting back whatever it was
+/// written over. The starting playfield can't be scraped.
+pub fn scrape(game: &mut Game, address: usize) {
+    if let Some(instruction) = game.history.scrape(address) {
+        game.instructions[address] = instruction;
+    }
+
+    game.peeled_layers = game.peeled_layers.min(game.history.depth());
+}
+
+/// Shows the playfield `delta` more layers back, between what is on it now
+/// and the starting playfield.
+pub fn peel(game: &mut Game, delta: i32) {
+    let peeled = game.peeled_layers as i32 + delta;
+    game.peeled_layers = peeled.max(0).min(game.history.depth() as i32) as usize;
+}
+
+/// Replaces the hand with a freshly dealt one.
+pub fn deal(game: &mut Game) {
+    game.cards = get_cards(&mut game.rng, &game.layout, &mut game.next_card_id);
+}
+
+/// Executes the instruction at `address` without waiting for the countdown.
+/// Returns the next address, or `None` if execution left the playfield.
+pub fn step(game: &mut Game, address: i32) -> Option<i32> {
+    if is_on_playfield(address) {
+        game.trace.push(address as usize);
+
+        let excess = game.trace.len().saturating_sub(TRACE_LENGTH);
+        game.trace.drain(..excess);
+    }
+
+    let new_address = execute(game, address);
+    if is_on_playfield(address) {
+        game.profile.record(address as usize, new_address);
+    }
+
+    if is_on_playfield(new_address) {
+        set_executing_address(game, new_address);
+
+        Some(new_address)
+    } else {
         game.executing_address = None;
-        game.instruction_countdown = COUNTDOWN_LENGTH;
 
-        game.paused = false;
+        None
     }
+}
 
-    draw(platform, game);
+/// Starts execution from the first address, as the Run button does.
+pub fn run(game: &mut Game) {
+    game.profile = profile::Profile::default();
+    set_executing_address(game, 0);
+}
 
-    false
+pub fn toggle_pause(game: &mut Game) {
+    game.paused = !game.paused;
+}
+
+/// Executes the current instruction straight away and pauses, or starts
+/// execution paused at the first address if nothing is executing.
+pub fn step_and_pause(game: &mut Game) {
+    match game.executing_address {
+        Some(address) => {
+            step(game, address);
+        }
+        None => run(game),
+    }
+
+    game.paused = true;
+}
+
+/// Stops execution, as the Break button does.
+pub fn stop(game: &mut Game) {
+    game.executing_address = None;
+    game.instruction_countdown = COUNTDOWN_LENGTH;
+
+    game.paused = false;
 }
 
 fn get_value(data: Data) -> u8 {
@@ -348,73 +528,72 @@ fn is_on_playfield(new_address: i32) -> bool {
 
 const CARD_UI_ID: UiId = 1;
 
-//calling this once will swallow multiple clicks on the button. We could either
-//pass in and return the number of clicks to fix that, or this could simply be
-//called multiple times per frame (once for each click).
-fn do_button(platform: &Platform,
-             context: &mut UIContext,
-             spec: &ButtonSpec,
-             id: UiId,
-             left_mouse_pressed: bool,
-             left_mouse_released: bool)
-             -> bool {
-    let mut result = false;
-
-    let mouse_pos = (platform.mouse_position)();
-    let inside = inside_rect(mouse_pos, spec.x, spec.y, spec.w, spec.h);
-
-    if context.active == id {
-        if left_mouse_released {
-            result = context.hot == id && inside;
-
-            context.set_not_active();
+/// The buttons and the trace list. These go before `draw`, which means they
+/// are drawn underneath the cards.
+pub fn do_controls(ui: &ui::Ui, game: &mut Game) {
+    if let Some(ref spec) = layout::button(&game.layout, 0, "Run") {
+        if ui.button(&mut game.ui_context, spec) {
+            run(game);
         }
-    } else if context.hot == id {
-        if left_mouse_pressed {
-            context.set_active(id);
+    }
+
+    let pause_text = if game.paused { "Resume" } else { "Pause" };
+    if let Some(ref spec) = layout::button(&game.layout, 1, pause_text) {
+        if ui.button(&mut game.ui_context, spec) {
+            toggle_pause(game);
         }
     }
 
-    if inside {
-        context.set_next_hot(id);
-    }
-
-    if context.active == id && (platform.key_pressed)(KeyCode::MouseLeft) {
-        draw_rect_with(platform,
-                       spec.x,
-                       spec.y,
-                       spec.w,
-                       spec.h,
-                       ["╔", "═", "╕", "║", "│", "╙", "─", "┘"]);
-    } else if context.hot == id {
-        draw_rect_with(platform,
-                       spec.x,
-                       spec.y,
-                       spec.w,
-                       spec.h,
-                       ["┌", "─", "╖", "│", "║", "╘", "═", "╝"]);
-    } else {
-        draw_rect(platform, spec.x, spec.y, spec.w, spec.h);
+    if let Some(ref spec) = layout::button(&game.layout, 2, "Break") {
+        if ui.button(&mut game.ui_context, spec) {
+            stop(game);
+        }
     }
 
-    let rect_middle = spec.x + (spec.w / 2);
+    do_trace(ui, game);
+}
 
-    (platform.print_xy)(rect_middle - (spec.text.len() as i32 / 2),
-                        spec.y + (spec.h / 2),
-                        &spec.text);
+//the most recently executed addresses, newest at the top. Clicking one
+//scrolls the playfield to it.
+fn do_trace(ui: &ui::Ui, game: &mut Game) {
+    let trace = game.layout.trace;
+    if trace.size.height <= 0 {
+        return;
+    }
 
-    return result;
+    ui.label(trace.top_left.x, trace.top_left.y, trace.size.width, "Trace");
+
+    let rows: Vec<String> = game.trace
+        .iter()
+        .rev()
+        .map(|&address| {
+            format!("{:#04X}{}{}",
+                    address,
+                    game.theme.glyphs("│"),
+                    game.instructions[address])
+        })
+        .collect();
+    let list = Rect::from_values(trace.top_left.x,
+                                 trace.top_left.y + 1,
+                                 trace.size.width,
+                                 trace.size.height - 1);
+
+    if let Some(row) = ui.list(&mut game.ui_context, list, "trace", &rows, &mut game.trace_list) {
+        let address = game.trace[game.trace.len() - 1 - row] as i32;
+        game.scroll_offset = address - game.layout.playfield.size.height / 2;
+    }
 }
 
 pub fn over_address(game: &Game, mouse_pos: Point) -> Option<usize> {
     let card_upper_left = mouse_pos.add(CARD_MOUSE_X_OFFSET, CARD_MOUSE_Y_OFFSET);
+    let playfield = game.layout.playfield;
 
-    if card_upper_left.x > game.playfield_right_edge {
+    if card_upper_left.x > playfield.top_right.x {
         return None;
     }
 
     //plus 1 to skip the top edge of the card
-    let address = game.scroll_offset + card_upper_left.y + 1;
+    let address = scroll_offset(game) + card_upper_left.y - playfield.top_left.y + 1;
 
     if address >= 0 && address < PLAYFIELD_SIZE as i32 {
         Some(address as usize)
@@ -426,6 +605,8 @@ pub fn over_address(game: &Game, mouse_pos: Point) -> Option<usize> {
 
 pub fn draw(platform: &Platform, game: &Game) {
     draw_instructions(platform, game);
+    arrows::draw(platform, game);
+    draw_heatmap(platform, game);
 
     let selected = game.selected_card.unwrap_or(std::usize::MAX);
 
@@ -433,57 +614,144 @@ pub fn draw(platform: &Platform, game: &Game) {
         let ref card = game.cards[i];
 
         if i != selected {
-            draw_card(platform, card);
+            draw_card(platform, &game.theme, card);
         }
     }
 
     if let Some(card) = game.cards.get(selected) {
-        let mouse_pos = (platform.mouse_position)();
+        let (card_upper_left, over_playfield) = match game.placement_cursor {
+            Some(address) => {
+                let playfield = game.layout.playfield;
+                let y = playfield.top_left.y + address as i32 - scroll_offset(game) - 1;
 
-        let card_upper_left = mouse_pos.add(CARD_MOUSE_X_OFFSET, CARD_MOUSE_Y_OFFSET);
+                (Point::new_safe(playfield.top_right.x, y), true)
+            }
+            None => {
+                let mouse_pos = (platform.mouse_position)();
 
-        draw_card_at(platform, card_upper_left, card);
+                (mouse_pos.add(CARD_MOUSE_X_OFFSET, CARD_MOUSE_Y_OFFSET),
+                 over_address(game, mouse_pos).is_some())
+            }
+        };
 
-        if over_address(game, mouse_pos).is_some() {
+        draw_card_at(platform, &game.theme, card_upper_left, card);
+
+        if over_playfield {
             (platform.print_xy)(card_upper_left.x, card_upper_left.y + 1, "<");
         }
     }
 
     draw_registers(platform, game);
+    provenance::draw(platform, game);
+
+    let registers = game.layout.registers;
+    if game.editor.enabled {
+        (platform.print_xy)(registers.top_left.x, registers.bottom_left.y, "EDITOR");
+    } else if game.peeled_layers > 0 {
+        (platform.print_xy)(registers.top_left.x,
+                            registers.bottom_left.y,
+                            &format!("LAYER -{}", game.peeled_layers));
+    }
 }
 
-const CARD_WIDTH: i32 = 16;
-const CARD_HEIGHT: i32 = 12;
+pub const CARD_WIDTH: i32 = 16;
+pub const CARD_HEIGHT: i32 = 12;
 
 const CARD_MOUSE_X_OFFSET: i32 = -CARD_WIDTH / 2;
 const CARD_MOUSE_Y_OFFSET: i32 = 0;
 
 
-fn draw_card(platform: &Platform, card: &Card) {
-    draw_card_at(platform, card.location, card);
+fn draw_card(platform: &Platform, theme: &Theme, card: &Card) {
+    draw_card_at(platform, theme, card.location, card);
 }
 
-fn draw_card_at(platform: &Platform, location: Point, card: &Card) {
+fn draw_card_at(platform: &Platform, theme: &Theme, location: Point, card: &Card) {
     let x = location.x;
     let y = location.y;
 
-    draw_rect(platform, x, y, CARD_WIDTH, CARD_HEIGHT);
+    draw_rect(platform, theme, x, y, CARD_WIDTH, CARD_HEIGHT);
 
     let mut index = 0;
     for i in (y + 1)..(y + CARD_HEIGHT - 1) {
         if let Some(instruction) = card.instructions.get(index) {
-            let mut instr_str = format!("{}", instruction);
-            instr_str.truncate(CARD_WIDTH as usize - 2);
-
-            (platform.print_xy)(x + 1, i, &instr_str);
+            draw_tokens(platform, theme, x + 1, i, CARD_WIDTH as usize - 2, instruction);
         }
 
         index += 1;
     }
 }
 
-fn draw_rect(platform: &Platform, x: i32, y: i32, w: i32, h: i32) {
+/// Prints `instruction` with each of its tokens in its own colour, cutting it
+/// off after `width` characters.
+pub fn draw_tokens(platform: &Platform,
+                   theme: &Theme,
+                   x: i32,
+                   y: i32,
+                   width: usize,
+                   instruction: &Instruction) {
+    let mut x = x;
+    let mut remaining = width;
+
+    for (token, text) in instruction.tokens() {
+        let text: String = text.chars().take(remaining).collect();
+        let length = text.chars().count();
+
+        (platform.set_colors)(theme.token_color(token), theme.background);
+        (platform.print_xy)(x, y, &text);
+
+        x += length as i32;
+        remaining -= length;
+    }
+
+    (platform.set_colors)(theme.text, theme.background);
+}
+
+/// Prints the `0x00│` in front of the instruction at `address` on the
+/// playfield, and returns how wide it is.
+pub fn draw_address(platform: &Platform,
+                    theme: &Theme,
+                    x: i32,
+                    y: i32,
+                    address: usize,
+                    highlight: bool)
+                    -> i32 {
+    if highlight {
+        draw_address_in(platform,
+                        theme,
+                        x,
+                        y,
+                        address,
+                        theme.highlight_text,
+                        theme.highlight_background)
+    } else {
+        draw_address_in(platform, theme, x, y, address, theme.address, theme.background)
+    }
+}
+
+//`draw_address` with the address itself in `foreground` on `background`.
+fn draw_address_in(platform: &Platform,
+                   theme: &Theme,
+                   x: i32,
+                   y: i32,
+                   address: usize,
+                   foreground: Color,
+                   background: Color)
+                   -> i32 {
+    let text = format!("{:#04X}", address);
+    let width = text.chars().count() as i32;
+
+    (platform.set_colors)(foreground, background);
+    (platform.print_xy)(x, y, &text);
+
+    (platform.set_colors)(theme.text, theme.background);
+    (platform.print_xy)(x + width, y, &theme.glyphs("│"));
+
+    width + 1
+}
+
+fn draw_rect(platform: &Platform, theme: &Theme, x: i32, y: i32, w: i32, h: i32) {
     draw_rect_with(platform,
+                   theme,
                    x,
                    y,
                    w,
@@ -491,8 +759,9 @@ fn draw_rect(platform: &Platform, x: i32, y: i32, w: i32, h: i32) {
                    ["┌", "─", "┐", "│", "│", "└", "─", "┘"]);
 }
 
-fn draw_double_line_rect(platform: &Platform, x: i32, y: i32, w: i32, h: i32) {
+fn draw_double_line_rect(platform: &Platform, theme: &Theme, x: i32, y: i32, w: i32, h: i32) {
     draw_rect_with(platform,
+                   theme,
                    x,
                    y,
                    w,
@@ -500,30 +769,38 @@ fn draw_double_line_rect(platform: &Platform, x: i32, y: i32, w: i32, h: i32) {
                    ["╔", "═", "╗", "║", "║", "╚", "═", "╝"]);
 }
 
-fn draw_rect_with(platform: &Platform, x: i32, y: i32, w: i32, h: i32, edges: [&str; 8]) {
+fn draw_rect_with(platform: &Platform,
+                  theme: &Theme,
+                  x: i32,
+                  y: i32,
+                  w: i32,
+                  h: i32,
+                  edges: [&str; 8]) {
     (platform.clear)(Some(Rect::from_values(x, y, w, h)));
 
+    let edges: Vec<String> = edges.iter().map(|edge| theme.glyphs(edge)).collect();
+
     let right = x + w - 1;
     let bottom = y + h - 1;
     // top
-    (platform.print_xy)(x, y, edges[0]);
+    (platform.print_xy)(x, y, &edges[0]);
     for i in (x + 1)..right {
-        (platform.print_xy)(i, y, edges[1]);
+        (platform.print_xy)(i, y, &edges[1]);
     }
-    (platform.print_xy)(right, y, edges[2]);
+    (platform.print_xy)(right, y, &edges[2]);
 
     // sides
     for i in (y + 1)..bottom {
-        (platform.print_xy)(x, i, edges[3]);
-        (platform.print_xy)(right, i, edges[4]);
+        (platform.print_xy)(x, i, &edges[3]);
+        (platform.print_xy)(right, i, &edges[4]);
     }
 
     //bottom
-    (platform.print_xy)(x, bottom, edges[5]);
+    (platform.print_xy)(x, bottom, &edges[5]);
     for i in (x + 1)..right {
-        (platform.print_xy)(i, bottom, edges[6]);
+        (platform.print_xy)(i, bottom, &edges[6]);
     }
-    (platform.print_xy)(right, bottom, edges[7]);
+    (platform.print_xy)(right, bottom, &edges[7]);
 }
 
 macro_rules! clamp {
@@ -547,41 +824,22 @@ pub fn clamp_scroll_offset(height: i32, scroll_offset: i32) -> i32 {
     clamp!(-height + 1, scroll_offset, len - 1)
 }
 
-const ALT_FG: Color = Color {
-    red: 0,
-    green: 0,
-    blue: 0,
-    alpha: 255,
-};
-const ALT_BG: Color = Color {
-    red: 255,
-    green: 255,
-    blue: 255,
-    alpha: 255,
-};
-const STANDARD_FG: Color = Color {
-    red: 255,
-    green: 255,
-    blue: 255,
-    alpha: 255,
-};
-const STANDARD_BG: Color = Color {
-    red: 0,
-    green: 0,
-    blue: 0,
-    alpha: 255,
-};
+/// The address shown at the top of the playfield panel.
+pub fn scroll_offset(game: &Game) -> i32 {
+    clamp_scroll_offset(game.layout.playfield.size.height, game.scroll_offset)
+}
 
 const REGISTERS_PER_ROW: i32 = 4;
 const REGISTER_DISPLAY_WIDTH: i32 = 8;
 const REGISTER_DISPLAY_HEIGHT: i32 = 1;
 const REGISTERS_X_OFFSET: i32 = REGISTER_DISPLAY_WIDTH * REGISTERS_PER_ROW;
-const REGISTERS_Y_OFFSET: i32 = 0;
 
 const GENERAL_REGISTER_DISPLAY_HEIGHT: i32 = ((REGISTER_AMOUNT as i32) / REGISTERS_PER_ROW);
 
 fn draw_registers(platform: &Platform, game: &Game) {
-    let width = (platform.size)().width - REGISTERS_X_OFFSET;
+    let registers = game.layout.registers;
+    let left = registers.top_left.x;
+    let top = registers.top_left.y;
 
     for y in 0..GENERAL_REGISTER_DISPLAY_HEIGHT {
         for x in 0..REGISTERS_PER_ROW {
@@ -590,12 +848,13 @@ fn draw_registers(platform: &Platform, game: &Game) {
             if let Some(register) = common::to_register(register_number) {
 
 
-                (platform.print_xy)((x * REGISTER_DISPLAY_WIDTH) + width,
-
-                                    (y * REGISTER_DISPLAY_HEIGHT) + REGISTERS_Y_OFFSET,
-                                    &format!("{:?}:{:#04X}",
-                                             register,
-                                             game.registers[register_number as usize]));
+                layout::print_in(platform,
+                                 registers,
+                                 (x * REGISTER_DISPLAY_WIDTH) + left,
+                                 (y * REGISTER_DISPLAY_HEIGHT) + top,
+                                 &format!("{:?}:{:#04X}",
+                                          register,
+                                          game.registers[register_number as usize]));
             }
         }
     }
@@ -606,36 +865,130 @@ fn draw_registers(platform: &Platform, game: &Game) {
         "IR:----".to_string()
     };
 
-    (platform.print_xy)(width,
-                        GENERAL_REGISTER_DISPLAY_HEIGHT + REGISTERS_Y_OFFSET,
-                        &ir_string);
+    layout::print_in(platform,
+                     registers,
+                     left,
+                     GENERAL_REGISTER_DISPLAY_HEIGHT + top,
+                     &ir_string);
+
+    layout::print_in(platform,
+                     registers,
+                     left + REGISTER_DISPLAY_WIDTH + 2,
+                     GENERAL_REGISTER_DISPLAY_HEIGHT + top,
+                     &seed_string(game));
+}
+
+fn seed_string(game: &Game) -> String {
+    if game.seed == game.daily_seed {
+        format!("Seed:{} daily", game.seed)
+    } else {
+        format!("Seed:{}", game.seed)
+    }
 }
 
 fn draw_instructions(platform: &Platform, game: &Game) {
 
-    let height = (platform.size)().height;
-    let scroll_offset = clamp_scroll_offset(height, game.scroll_offset);
+    let playfield = game.layout.playfield;
+    let x = playfield.top_left.x;
+    let scroll_offset = scroll_offset(game);
+    let theme = &game.theme;
+    let analysis = analysis::analyse(&game.instructions);
 
-    for y in 0..height {
-        let address = y + scroll_offset;
+    for row in 0..playfield.size.height {
+        let y = playfield.top_left.y + row;
+        let address = row + scroll_offset;
         if let Some(instruction) = game.instructions.get(address as usize) {
-            if Some(address) == game.executing_address {
-                (platform.set_colors)(ALT_FG, ALT_BG);
-                (platform.print_xy)(0, y, format!("{:#04X}│{}", address, instruction).as_ref());
-                (platform.set_colors)(STANDARD_FG, STANDARD_BG);
+            if game.editor.enabled {
+                editor::draw_instruction(platform, game, y, address as usize);
+            } else if game.peeled_layers > 0 {
+                draw_peeled_instruction(platform, game, y, address as usize);
+            } else if Some(address) == game.executing_address {
+                let line = format!("{:#04X}{}{}", address, theme.glyphs("│"), instruction);
+
+                (platform.set_colors)(theme.highlight_text, theme.highlight_background);
+                (platform.print_xy)(x, y, &line);
+                (platform.set_colors)(theme.text, theme.background);
+            } else if !analysis.reachable[address as usize] {
+                //dead code is all one colour, so it stands out from the rest.
+                let width = draw_address(platform, theme, x, y, address as usize, false);
+
+                (platform.set_colors)(theme.dead, theme.background);
+                (platform.print_xy)(x + width, y, &instruction.to_string());
+                (platform.set_colors)(theme.text, theme.background);
             } else {
-                (platform.print_xy)(0, y, format!("{:#04X}│{}", address, instruction).as_ref());
+                let width = draw_address(platform, theme, x, y, address as usize, false);
+                draw_tokens(platform, theme, x + width, y, usize::MAX, instruction);
             }
         } else if address == -1 {
-            (platform.print_xy)(0, y, "────┐");
+            (platform.print_xy)(x, y, &theme.glyphs("────┐"));
         } else if address == common::PLAYFIELD_SIZE as i32 {
-            (platform.print_xy)(0, y, "────┘");
+            (platform.print_xy)(x, y, &theme.glyphs("────┘"));
         } else {
             //don't print anything
         }
     }
 }
 
+/// The instruction the playfield shows at `address`, which is the one there
+/// now unless its layers are being peeled back.
+pub fn shown_instruction(game: &Game, address: usize) -> Instruction {
+    match game.history.peeled(address, game.peeled_layers) {
+        Some(layer) => layer.instruction,
+        None => game.instructions[address],
+    }
+}
+
+//what was at `address` `game.peeled_layers` layers ago, with the address in
+//the peeled colour if that has since been written over.
+fn draw_peeled_instruction(platform: &Platform, game: &Game, y: i32, address: usize) {
+    let theme = &game.theme;
+    let x = game.layout.playfield.top_left.x;
+    let buried = game.history.layers(address).len() > 1;
+    let width = if buried {
+        draw_address_in(platform, theme, x, y, address, theme.peeled, theme.background)
+    } else {
+        draw_address(platform, theme, x, y, address, false)
+    };
+
+    draw_tokens(platform, theme, x + width, y, usize::MAX, &shown_instruction(game, address));
+}
+
+//a column beside the playfield coloured by how often each address has been
+//executed, see `Profile::heat`.
+fn draw_heatmap(platform: &Platform, game: &Game) {
+    let heatmap = game.layout.heatmap;
+    if heatmap.size.width <= 0 {
+        return;
+    }
+
+    let theme = &game.theme;
+    let scroll_offset = scroll_offset(game);
+
+    for row in 0..heatmap.size.height {
+        let address = row + scroll_offset;
+        if !is_on_playfield(address) || game.profile.counts[address as usize] == 0 {
+            continue;
+        }
+
+        (platform.set_colors)(theme.text, heat_color(game, address as usize));
+        (platform.print_xy)(heatmap.top_left.x, heatmap.top_left.y + row, " ");
+    }
+
+    (platform.set_colors)(theme.text, theme.background);
+}
+
+/// The colour of the heatmap beside `address`, which is the background if it
+/// hasn't been executed.
+pub fn heat_color(game: &Game, address: usize) -> Color {
+    let theme = &game.theme;
+
+    if game.profile.counts.get(address).cloned().unwrap_or(0) == 0 {
+        theme.background
+    } else {
+        theme.heat_cold.mix(theme.heat_hot, game.profile.heat(address))
+    }
+}
+
 pub fn clicked_card(game: &Game, mouse_position: Point) -> Option<usize> {
     //we iterate thisbackwards because we want the top one (the last drawn)
     //and the cards are drawn in forwards order,
@@ -0,0 +1,184 @@
+use rand::Rng;
+
+use common::*;
+
+use {do_controls, draw, new_game};
+use ui::{Input, Ui};
+
+//Ctrl+R brings this screen up instead of immediately restarting, so a seed can
+//be chosen. Teammates who start with the same seed get the same deals, and
+//the daily seed makes that easy to arrange without passing numbers around.
+
+const DIALOG_WIDTH: i32 = 44;
+const DIALOG_HEIGHT: i32 = 20;
+
+const BUTTON_WIDTH: i32 = 9;
+const BUTTON_HEIGHT: i32 = 3;
+
+//enough digits for any `Seed`
+const MAX_SEED_DIGITS: usize = 10;
+
+//the seeds games were last started with from here are kept in storage, one
+//per line, newest first, so they can be picked again later.
+const RECENT_SEEDS_BLOB: &str = "recent-seeds";
+const MAX_RECENT_SEEDS: usize = 5;
+
+//returns true if quit requested
+pub fn update_and_render(platform: &Platform, game: &mut Game, events: &[Event]) -> bool {
+    for event in events {
+        match *event {
+            Event::KeyPressed { key: KeyCode::Escape, ctrl: _, shift: _ } => {
+                game.new_game_screen = None;
+                return false;
+            }
+            Event::Close => return true,
+            _ => (),
+        }
+    }
+
+    let input = Input::new(platform, events);
+    let ui = Ui::new(platform, &input, game.theme);
+
+    game.ui_context.frame_init();
+
+    //the game carries on being drawn behind the dialog, but can't be used.
+    do_controls(&ui.disabled(), game);
+    draw(platform, game);
+
+    let inside = ui.modal(DIALOG_WIDTH, DIALOG_HEIGHT, "New Game");
+    let x = inside.top_left.x + 1;
+    let y = inside.top_left.y;
+    let width = inside.size.width - 2;
+
+    let mut chosen_seed = None;
+
+    let mut seed_text = game.new_game_screen
+        .as_ref()
+        .map_or(String::new(), |screen| screen.seed_text.clone());
+    if ui.text_input(&mut game.ui_context, x, y + 1, width, "Seed", &mut seed_text, is_seed) {
+        chosen_seed = Some(typed_seed(game, &seed_text));
+    }
+    if let Some(ref mut screen) = game.new_game_screen {
+        screen.seed_text = seed_text.clone();
+    }
+
+    ui.label(x, y + 2, width, "Leave it blank for a random seed.");
+
+    let buttons_y = y + 4;
+    let button_x = |index: i32| x + index * (BUTTON_WIDTH + 1);
+
+    for (index, text) in ["Start", "Random", "Daily", "Cancel"].iter().enumerate() {
+        let spec = ButtonSpec {
+            x: button_x(index as i32),
+            y: buttons_y,
+            w: BUTTON_WIDTH,
+            h: BUTTON_HEIGHT,
+            text: text.to_string(),
+        };
+
+        if ui.button(&mut game.ui_context, &spec) {
+            match index {
+                0 => chosen_seed = Some(typed_seed(game, &seed_text)),
+                1 => chosen_seed = Some(game.rng.gen()),
+                2 => chosen_seed = Some(game.daily_seed),
+                _ => {
+                    game.new_game_screen = None;
+                    return false;
+                }
+            }
+        }
+    }
+
+    ui.label(x,
+             buttons_y + BUTTON_HEIGHT + 1,
+             width,
+             &format!("Today's seed: {}", game.daily_seed));
+    ui.label(x,
+             buttons_y + BUTTON_HEIGHT + 2,
+             width,
+             &format!("Current seed: {}", game.seed));
+
+    let recent_y = buttons_y + BUTTON_HEIGHT + 4;
+    ui.label(x, recent_y, width, "Recent seeds:");
+
+    let daily_seed = game.daily_seed;
+    if let Some(ref mut screen) = game.new_game_screen {
+        let recent_seeds = screen.recent_seeds.get_or_insert_with(|| read_recent_seeds(platform));
+        let rows: Vec<String> = recent_seeds.iter()
+            .map(|&seed| if seed == daily_seed {
+                format!("{} daily", seed)
+            } else {
+                seed.to_string()
+            })
+            .collect();
+        let list = Rect::from_values(x, recent_y + 1, width, MAX_RECENT_SEEDS as i32);
+
+        if let Some(row) = ui.list(&mut game.ui_context,
+                                   list,
+                                   "recent seeds",
+                                   &rows,
+                                   &mut screen.recent_list) {
+            screen.seed_text = recent_seeds[row].to_string();
+        }
+    }
+
+    if let Some(seed) = chosen_seed {
+        remember_seed(platform, game, seed);
+        (platform.log)(&format!("starting a new game with seed {}", seed));
+
+        let bindings = game.bindings.clone();
+        let theme = game.theme;
+        *game = new_game(get_instructions(), (platform.size)(), seed, game.daily_seed);
+        game.bindings = bindings;
+        game.theme = theme;
+    }
+
+    false
+}
+
+//the seed typed in, or a random one if nothing is.
+fn typed_seed(game: &mut Game, seed_text: &str) -> Seed {
+    match seed_text.parse() {
+        Ok(seed) => seed,
+        Err(_) => game.rng.gen(),
+    }
+}
+
+fn is_seed(text: &str) -> bool {
+    text.len() <= MAX_SEED_DIGITS && text.chars().all(|c| c.is_ascii_digit()) &&
+    text.parse::<Seed>().is_ok()
+}
+
+fn read_recent_seeds(platform: &Platform) -> Vec<Seed> {
+    match (platform.read_blob)(RECENT_SEEDS_BLOB) {
+        Ok(Some(bytes)) => {
+            String::from_utf8_lossy(&bytes)
+                .lines()
+                .filter_map(|line| line.trim().parse().ok())
+                .take(MAX_RECENT_SEEDS)
+                .collect()
+        }
+        Ok(None) => Vec::new(),
+        Err(error) => {
+            (platform.log)(&format!("couldn't read the recent seeds: {}", error));
+            Vec::new()
+        }
+    }
+}
+
+//puts `seed` at the top of the recent seeds in storage.
+fn remember_seed(platform: &Platform, game: &mut Game, seed: Seed) {
+    let mut recent_seeds = match game.new_game_screen {
+        Some(NewGameScreen { recent_seeds: Some(ref recent_seeds), .. }) => recent_seeds.clone(),
+        _ => read_recent_seeds(platform),
+    };
+
+    recent_seeds.retain(|&recent| recent != seed);
+    recent_seeds.insert(0, seed);
+    recent_seeds.truncate(MAX_RECENT_SEEDS);
+
+    let text: String = recent_seeds.iter().map(|seed| format!("{}\n", seed)).collect();
+    if let Err(error) = (platform.write_blob)(RECENT_SEEDS_BLOB, text.as_bytes()) {
+        (platform.log)(&format!("couldn't save the recent seeds: {}", error));
+    }
+}
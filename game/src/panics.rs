@@ -0,0 +1,29 @@
+//A panic in the game has to be caught before it unwinds out of this crate. With
+//hot reloading the host has its own copy of std, and a panic coming from a
+//different copy of std can't be caught, it aborts the whole process. The
+//default panic hook would also print the message over the top of the game, so
+//while catching, a hook that keeps the message for the host to show is
+//swapped in. It is swapped back out afterwards, otherwise the hook would be
+//left pointing into this library after it had been unloaded.
+
+use std::cell::RefCell;
+use std::panic::{self, AssertUnwindSafe};
+
+thread_local!(static MESSAGE: RefCell<Option<String>> = const { RefCell::new(None) });
+
+/// Calls `f`, returning what it panicked with, along with where, if it did.
+pub fn catch<T, F: FnOnce() -> T>(f: F) -> Result<T, String> {
+    let previous_hook = panic::take_hook();
+    panic::set_hook(Box::new(|info| {
+        MESSAGE.with(|message| *message.borrow_mut() = Some(info.to_string()));
+    }));
+
+    let result = panic::catch_unwind(AssertUnwindSafe(f));
+
+    panic::set_hook(previous_hook);
+
+    result.map_err(|_| {
+        MESSAGE.with(|message| message.borrow_mut().take())
+            .unwrap_or_else(|| "the game panicked".to_string())
+    })
+}
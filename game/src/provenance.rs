@@ -0,0 +1,78 @@
+use common::*;
+
+use {editor, heat_color, scroll_offset};
+
+//Pointing at an address on the playfield shows where what is there came from,
+//in a tooltip beside it, and outlines every address the same card wrote to in
+//the heatmap column:
+//
+//    0x04│add  0x12 A┐ card 3, player 1, turn 2, 0:41
+//    0x05│NOP        │
+//    0x06│JNZ  0x04 A┘
+//
+//While the playfield's layers are peeled back, it is the layer being shown
+//that is described. Nothing is shown while a card is being placed, since the
+//card is in the way.
+
+pub fn draw(platform: &Platform, game: &Game) {
+    if game.selected_card.is_some() {
+        return;
+    }
+
+    let address = match editor::address_at(game, (platform.mouse_position)()) {
+        Some(address) => address,
+        None => return,
+    };
+    let placement = game.history
+        .peeled(address, game.peeled_layers)
+        .and_then(|layer| layer.placement);
+
+    if let Some(placement) = placement {
+        draw_footprint(platform, game, &game.history.footprint(placement.card));
+    }
+
+    let text = match placement {
+        Some(placement) => format!(" {} ", placement),
+        None => " starting playfield ".to_string(),
+    };
+    let theme = &game.theme;
+    let x = game.layout.heatmap.top_right.x.max(game.layout.playfield.top_right.x);
+    let y = game.layout.playfield.top_left.y + address as i32 - scroll_offset(game);
+
+    (platform.set_colors)(theme.highlight_text, theme.highlight_background);
+    (platform.print_xy)(x, y, &text);
+    (platform.set_colors)(theme.text, theme.background);
+}
+
+//a bracket down the heatmap column beside `footprint`, in one piece for each
+//run of addresses next to each other.
+fn draw_footprint(platform: &Platform, game: &Game, footprint: &[usize]) {
+    let heatmap = game.layout.heatmap;
+    if heatmap.size.width <= 0 {
+        return;
+    }
+
+    let theme = &game.theme;
+    let scroll_offset = scroll_offset(game);
+
+    for &address in footprint {
+        let row = address as i32 - scroll_offset;
+        if row < 0 || row >= heatmap.size.height {
+            continue;
+        }
+
+        let above = address > 0 && footprint.contains(&(address - 1));
+        let below = footprint.contains(&(address + 1));
+        let glyph = match (above, below) {
+            (false, false) => "<",
+            (false, true) => "┐",
+            (true, true) => "│",
+            (true, false) => "┘",
+        };
+
+        (platform.set_colors)(theme.footprint, heat_color(game, address));
+        (platform.print_xy)(heatmap.top_left.x, heatmap.top_left.y + row, &theme.glyphs(glyph));
+    }
+
+    (platform.set_colors)(theme.text, theme.background);
+}
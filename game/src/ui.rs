@@ -0,0 +1,546 @@
+use std::collections::hash_map::DefaultHasher;
+use std::hash::{Hash, Hasher};
+use std::panic::Location;
+
+use common::*;
+
+use common::theme::Theme;
+
+use {draw_double_line_rect, draw_rect, draw_rect_with, layout};
+
+//An immediate mode UI: each widget is a function called every frame, which
+//draws the widget and returns what the player did with it that frame. The only
+//state kept between frames is in `UIContext`, which remembers which widget the
+//mouse is over (hot), which one is being clicked or dragged (active) and which
+//one is being typed into (focused), along with anything the caller keeps for
+//a particular widget, like a `ListState`.
+//
+//Widgets are told apart by ids made from where the widget function was called
+//from and its label, so they don't need to be picked by hand. Widgets created
+//in a loop from the same line need different labels. Generated ids are always
+//negative, so they never collide with hand-picked ones like `CARD_UI_ID`.
+
+/// What the widgets need to know about this frame's input.
+pub struct Input {
+    pub mouse: Point,
+    pub left_pressed: bool,
+    pub left_released: bool,
+    pub left_down: bool,
+    pub scroll: i32,
+    //keys pressed without Ctrl, and whether Shift was held, in order.
+    pub keys: Vec<(KeyCode, bool)>,
+}
+
+impl Input {
+    pub fn new(platform: &Platform, events: &[Event]) -> Self {
+        let mut input = Input {
+            mouse: (platform.mouse_position)(),
+            left_pressed: false,
+            left_released: false,
+            left_down: (platform.key_pressed)(KeyCode::MouseLeft),
+            scroll: 0,
+            keys: Vec::new(),
+        };
+
+        for event in events {
+            match *event {
+                Event::KeyPressed { key: KeyCode::MouseLeft, ctrl: _, shift: _ } => {
+                    input.left_pressed = true;
+                }
+                Event::KeyReleased { key: KeyCode::MouseLeft, ctrl: _, shift: _ } => {
+                    input.left_released = true;
+                }
+                Event::KeyPressed { key, ctrl: false, shift } => input.keys.push((key, shift)),
+                Event::MouseScroll { delta } => input.scroll += delta,
+                _ => {}
+            }
+        }
+
+        input
+    }
+}
+
+/// Draws widgets with `platform` in the colours of `theme`, reacting to
+/// `input`.
+#[derive(Clone, Copy)]
+pub struct Ui<'a> {
+    pub platform: &'a Platform,
+    pub input: &'a Input,
+    //a copy, so the game can still be changed while widgets are drawn.
+    pub theme: Theme,
+    enabled: bool,
+}
+
+impl<'a> Ui<'a> {
+    pub fn new(platform: &'a Platform, input: &'a Input, theme: Theme) -> Self {
+        Ui {
+            platform,
+            input,
+            theme,
+            enabled: true,
+        }
+    }
+
+    /// The same widgets drawn this way ignore input, like the ones behind a
+    /// modal dialog should.
+    pub fn disabled(self) -> Self {
+        Ui { enabled: false, ..self }
+    }
+
+    fn hovered(&self, rect: Rect) -> bool {
+        self.enabled && layout::contains(rect, self.input.mouse)
+    }
+
+    //the usual hot/active dance. Returns true if the widget was clicked, which
+    //means the mouse was pressed and released over it.
+    fn click(&self, context: &mut UIContext, id: UiId, rect: Rect) -> bool {
+        let hovered = self.hovered(rect);
+        let mut clicked = false;
+
+        if context.active == id {
+            if self.input.left_released {
+                clicked = context.hot == id && hovered;
+
+                context.set_not_active();
+            }
+        } else if context.hot == id && self.input.left_pressed && self.enabled {
+            context.set_active(id);
+        }
+
+        if hovered {
+            context.set_next_hot(id);
+        }
+
+        clicked
+    }
+
+    /// Prints `text` at `x`, `y`, cut off after `width` columns.
+    pub fn label(&self, x: i32, y: i32, width: i32, text: &str) {
+        layout::print_in(self.platform,
+                         Rect::from_values(x.max(0), y.max(0), width.max(0), 1),
+                         x,
+                         y,
+                         text);
+    }
+
+    #[track_caller]
+    pub fn button(&self, context: &mut UIContext, spec: &ButtonSpec) -> bool {
+        let id = id_at(Location::caller(), &spec.text);
+        let rect = Rect::from_values(spec.x, spec.y, spec.w, spec.h);
+
+        let clicked = self.click(context, id, rect);
+
+        if context.active == id && self.input.left_down {
+            draw_rect_with(self.platform,
+                           &self.theme,
+                           spec.x,
+                           spec.y,
+                           spec.w,
+                           spec.h,
+                           ["╔", "═", "╕", "║", "│", "╙", "─", "┘"]);
+        } else if context.hot == id {
+            draw_rect_with(self.platform,
+                           &self.theme,
+                           spec.x,
+                           spec.y,
+                           spec.w,
+                           spec.h,
+                           ["┌", "─", "╖", "│", "║", "╘", "═", "╝"]);
+        } else {
+            draw_rect(self.platform, &self.theme, spec.x, spec.y, spec.w, spec.h);
+        }
+
+        let rect_middle = spec.x + (spec.w / 2);
+
+        (self.platform.print_xy)(rect_middle - (spec.text.len() as i32 / 2),
+                                 spec.y + (spec.h / 2),
+                                 &spec.text);
+
+        clicked
+    }
+
+    /// A `[x] label` checkbox. Returns true if it was flipped this frame.
+    #[allow(dead_code)]
+    #[track_caller]
+    pub fn toggle(&self,
+                  context: &mut UIContext,
+                  x: i32,
+                  y: i32,
+                  label: &str,
+                  value: &mut bool)
+                  -> bool {
+        let id = id_at(Location::caller(), label);
+        let rect = Rect::from_values(x, y, label.chars().count() as i32 + 4, 1);
+
+        let clicked = self.click(context, id, rect);
+        if clicked {
+            *value = !*value;
+        }
+
+        let text = format!("[{}] {}", if *value { "x" } else { " " }, label);
+        self.highlighted(context.hot == id, || (self.platform.print_xy)(x, y, &text));
+
+        clicked
+    }
+
+    /// A `label [=====-----] value` bar that can be clicked or dragged along,
+    /// or scrolled over. Returns true if `value` changed this frame.
+    #[allow(dead_code, clippy::too_many_arguments)]
+    #[track_caller]
+    pub fn slider(&self,
+                  context: &mut UIContext,
+                  x: i32,
+                  y: i32,
+                  width: i32,
+                  label: &str,
+                  value: &mut i32,
+                  min: i32,
+                  max: i32)
+                  -> bool {
+        let id = id_at(Location::caller(), label);
+        let old_value = *value;
+
+        let bar_x = x + label.chars().count() as i32 + 2;
+        let bar_width = (width - (bar_x - x) - (max.to_string().len() as i32 + 2)).max(1);
+        let bar = Rect::from_values(bar_x, y, bar_width, 1);
+
+        self.click(context, id, bar);
+
+        if context.active == id && self.input.left_down && max > min {
+            let along = (self.input.mouse.x - bar_x).max(0).min(bar_width - 1);
+            *value = min + along * (max - min) / (bar_width - 1).max(1);
+        } else if context.hot == id {
+            *value -= self.input.scroll;
+        }
+        *value = (*value).max(min).min(max);
+
+        let filled = if max > min {
+            ((*value - min) * bar_width / (max - min)) as usize
+        } else {
+            0
+        };
+        let text = format!("{} [{}{}] {}",
+                           label,
+                           "=".repeat(filled),
+                           "-".repeat(bar_width as usize - filled),
+                           value);
+        self.highlighted(context.hot == id || context.active == id,
+                         || self.label(x, y, width, &text));
+
+        *value != old_value
+    }
+
+    /// A one line text box, which takes the keyboard once clicked, or if
+    /// nothing else has it. Typed characters are only kept if `accept`
+    /// returns true for the resulting text. Returns true when Enter is
+    /// pressed while it has the keyboard.
+    #[allow(clippy::too_many_arguments)]
+    #[track_caller]
+    pub fn text_input(&self,
+                      context: &mut UIContext,
+                      x: i32,
+                      y: i32,
+                      width: i32,
+                      label: &str,
+                      text: &mut String,
+                      accept: fn(&str) -> bool)
+                      -> bool {
+        let id = id_at(Location::caller(), label);
+        let rect = Rect::from_values(x, y, width, 1);
+
+        if self.enabled {
+            if self.input.left_pressed {
+                if self.hovered(rect) {
+                    context.focused = id;
+                } else if context.focused == id {
+                    context.focused = 0;
+                }
+            }
+
+            if context.focused == 0 {
+                context.focused = id;
+            }
+        }
+
+        let focused = self.enabled && context.focused == id;
+        let mut submitted = false;
+
+        if focused {
+            for &(key, shift) in self.input.keys.iter() {
+                match key {
+                    KeyCode::Backspace => {
+                        text.pop();
+                    }
+                    KeyCode::Enter | KeyCode::NumEnter => submitted = true,
+                    _ => {
+                        if let Some(character) = character(key, shift) {
+                            let mut new_text = text.clone();
+                            new_text.push(character);
+
+                            if accept(&new_text) {
+                                *text = new_text;
+                            }
+                        }
+                    }
+                }
+            }
+        }
+
+        //the cursor blinks once a second.
+        let blink_on = (self.platform.time)().subsec_millis() < 500;
+        let cursor = if focused && blink_on { "_" } else { "" };
+        self.label(x, y, width, &format!("{}: {}{}", label, text, cursor));
+
+        submitted
+    }
+
+    /// Shows as many of `rows` as fit in `rect`, scrolled by the mouse wheel
+    /// while the mouse is over it. Returns the index of the row clicked on
+    /// this frame, if any.
+    #[track_caller]
+    pub fn list(&self,
+                context: &mut UIContext,
+                rect: Rect,
+                label: &str,
+                rows: &[String],
+                state: &mut ListState)
+                -> Option<usize> {
+        let id = id_at(Location::caller(), label);
+        let height = rect.size.height.max(0) as usize;
+
+        if self.hovered(rect) {
+            context.set_next_hot(id);
+        }
+
+        let hot = context.hot == id;
+        if hot {
+            state.scroll = (state.scroll as i32 + self.input.scroll).max(0) as usize;
+        }
+        state.scroll = state.scroll.min(rows.len().saturating_sub(height));
+
+        let clicked = if hot && self.input.left_pressed {
+            let row = state.scroll + (self.input.mouse.y - rect.top_left.y) as usize;
+
+            if row < rows.len() { Some(row) } else { None }
+        } else {
+            None
+        };
+
+        let x = rect.top_left.x;
+        for (y, row) in (rect.top_left.y..rect.bottom_left.y).zip(rows.iter().skip(state.scroll)) {
+            let row_hovered = hot && self.input.mouse.y == y;
+            self.highlighted(row_hovered, || layout::print_in(self.platform, rect, x, y, row));
+        }
+
+        //show that there is more above or below.
+        let right = rect.top_right.x - 1;
+        if state.scroll > 0 {
+            let up = self.theme.glyphs("↑");
+            layout::print_in(self.platform, rect, right, rect.top_left.y, &up);
+        }
+        if state.scroll + height < rows.len() {
+            let down = self.theme.glyphs("↓");
+            layout::print_in(self.platform, rect, right, rect.bottom_left.y - 1, &down);
+        }
+
+        clicked
+    }
+
+    /// Clears a box in the middle of the screen and draws a titled frame
+    /// around it, returning the space inside the frame. The widgets behind it
+    /// should be drawn with `disabled`, so only the ones inside respond.
+    pub fn modal(&self, width: i32, height: i32, title: &str) -> Rect {
+        let size = (self.platform.size)();
+        let width = width.min(size.width);
+        let height = height.min(size.height);
+        let x = ((size.width - width) / 2).max(0);
+        let y = ((size.height - height) / 2).max(0);
+
+        draw_double_line_rect(self.platform, &self.theme, x, y, width, height);
+        self.label(x + 2, y, width - 4, &format!(" {} ", title));
+
+        Rect::from_values(x + 1, y + 1, (width - 2).max(0), (height - 2).max(0))
+    }
+
+    fn highlighted<F: FnOnce()>(&self, highlight: bool, draw: F) {
+        if highlight {
+            (self.platform.set_colors)(self.theme.highlight_text, self.theme.highlight_background);
+            draw();
+            (self.platform.set_colors)(self.theme.text, self.theme.background);
+        } else {
+            draw();
+        }
+    }
+}
+
+/// An id for a widget labelled `label`, created where this is called from.
+#[allow(dead_code)]
+#[track_caller]
+pub fn id(label: &str) -> UiId {
+    id_at(Location::caller(), label)
+}
+
+fn id_at(location: &Location, label: &str) -> UiId {
+    let mut hasher = DefaultHasher::new();
+    location.file().hash(&mut hasher);
+    location.line().hash(&mut hasher);
+    location.column().hash(&mut hasher);
+    label.hash(&mut hasher);
+
+    (hasher.finish() as u32 | 0x8000_0000) as i32
+}
+
+//the character a key types, on a US layout.
+fn character(key: KeyCode, shift: bool) -> Option<char> {
+    let letters = [KeyCode::A, KeyCode::B, KeyCode::C, KeyCode::D, KeyCode::E, KeyCode::F,
+                   KeyCode::G, KeyCode::H, KeyCode::I, KeyCode::J, KeyCode::K, KeyCode::L,
+                   KeyCode::M, KeyCode::N, KeyCode::O, KeyCode::P, KeyCode::Q, KeyCode::R,
+                   KeyCode::S, KeyCode::T, KeyCode::U, KeyCode::V, KeyCode::W, KeyCode::X,
+                   KeyCode::Y, KeyCode::Z];
+    if let Some(index) = letters.iter().position(|&letter| letter == key) {
+        let character = (b'a' + index as u8) as char;
+
+        return Some(if shift { character.to_ascii_uppercase() } else { character });
+    }
+
+    let digits = [(KeyCode::Row0, KeyCode::Num0),
+                  (KeyCode::Row1, KeyCode::Num1),
+                  (KeyCode::Row2, KeyCode::Num2),
+                  (KeyCode::Row3, KeyCode::Num3),
+                  (KeyCode::Row4, KeyCode::Num4),
+                  (KeyCode::Row5, KeyCode::Num5),
+                  (KeyCode::Row6, KeyCode::Num6),
+                  (KeyCode::Row7, KeyCode::Num7),
+                  (KeyCode::Row8, KeyCode::Num8),
+                  (KeyCode::Row9, KeyCode::Num9)];
+    let digit = digits.iter().position(|&(row, num)| key == num || (key == row && !shift));
+    if let Some(index) = digit {
+        return Some((b'0' + index as u8) as char);
+    }
+
+    match (key, shift) {
+        (KeyCode::Space, _) => Some(' '),
+        (KeyCode::Minus, false) |
+        (KeyCode::NumMinus, _) => Some('-'),
+        (KeyCode::Period, false) |
+        (KeyCode::NumPeriod, _) => Some('.'),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::cell::RefCell;
+
+    use common::*;
+
+    use super::{id, Input, Ui};
+
+    #[derive(Default)]
+    struct Widgets {
+        sound: bool,
+        volume: i32,
+        //the id made by the same call each frame.
+        ids: Vec<UiId>,
+    }
+
+    thread_local! {
+        static WIDGETS: RefCell<Widgets> = RefCell::new(Widgets::default());
+    }
+
+    //a frame with a toggle on the top row and a slider under it. It takes a
+    //`Vec` to be an `UpdateAndRender`.
+    #[allow(clippy::ptr_arg)]
+    fn frame(platform: &Platform, game: &mut Game, events: &mut Vec<Event>) -> bool {
+        let input = Input::new(platform, events);
+        let ui = Ui::new(platform, &input, game.theme);
+        game.ui_context.frame_init();
+
+        WIDGETS.with(|widgets| {
+            let widgets = &mut *widgets.borrow_mut();
+
+            ui.toggle(&mut game.ui_context, 0, 0, "sound", &mut widgets.sound);
+            ui.slider(&mut game.ui_context, 0, 1, 30, "volume", &mut widgets.volume, 0, 10);
+            widgets.ids.push(id("sound"));
+        });
+
+        false
+    }
+
+    fn play(frames: Vec<Vec<Event>>) -> Widgets {
+        let size = Size::new(40, 4);
+        recording::reset(size);
+        let platform = recording::platform();
+        let mut game = ::new_game(get_instructions(), size, DEFAULT_SEED, DEFAULT_SEED);
+
+        recording::play(frame, &platform, &mut game, frames);
+
+        WIDGETS.with(|widgets| {
+            let widgets = widgets.borrow();
+            Widgets {
+                sound: widgets.sound,
+                volume: widgets.volume,
+                ids: widgets.ids.clone(),
+            }
+        })
+    }
+
+    fn set_volume(volume: i32) {
+        WIDGETS.with(|widgets| widgets.borrow_mut().volume = volume);
+    }
+
+    #[test]
+    fn toggle_flips_when_clicked() {
+        let widgets = play(recording::click(1, 0));
+
+        assert!(widgets.sound);
+        assert_eq!(recording::screen().lines().next(), Some("[x] sound"));
+
+        let widgets = play(recording::click(1, 0));
+
+        assert!(!widgets.sound);
+        assert_eq!(recording::screen().lines().next(), Some("[ ] sound"));
+    }
+
+    #[test]
+    fn slider_follows_a_drag() {
+        //`volume [` puts the bar at 8, and `] 10` leaves it 18 wide.
+        set_volume(5);
+        let widgets = play(vec![vec![Event::MouseMove { x: 8, y: 1 }],
+                                vec![recording::key(KeyCode::MouseLeft)]]);
+        assert_eq!(widgets.volume, 0);
+
+        set_volume(5);
+        let mut frames = vec![vec![Event::MouseMove { x: 8, y: 1 }],
+                              vec![recording::key(KeyCode::MouseLeft)],
+                              vec![Event::MouseMove { x: 25, y: 1 }]];
+        let widgets = play(frames.clone());
+        assert_eq!(widgets.volume, 10);
+        assert_eq!(recording::screen().lines().nth(1),
+                   Some("volume [==================] 10"));
+
+        //releasing the button lets go of it, and scrolling over it moves it.
+        set_volume(5);
+        frames.push(vec![Event::KeyReleased {
+                             key: KeyCode::MouseLeft,
+                             ctrl: false,
+                             shift: false,
+                         }]);
+        frames.push(vec![Event::MouseMove { x: 10, y: 1 }]);
+        frames.push(vec![Event::MouseScroll { delta: 1 }]);
+        let widgets = play(frames);
+        assert_eq!(widgets.volume, 9);
+    }
+
+    #[test]
+    fn ids_are_stable_across_frames() {
+        let widgets = play(recording::wait(3));
+
+        assert_eq!(widgets.ids.len(), 3);
+        assert!(widgets.ids.iter().all(|&id| id == widgets.ids[0]));
+        assert!(widgets.ids[0] < 0);
+
+        let labelled: Vec<UiId> = ["a", "b"].iter().map(|label| id(label)).collect();
+        assert!(labelled[0] != labelled[1]);
+    }
+}
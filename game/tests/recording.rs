@@ -0,0 +1,28 @@
+extern crate common;
+extern crate game;
+
+use common::*;
+
+//drives the game through the recording platform, and compares what ends up
+//on screen with a fixture. If the screen changes on purpose, the fixture can
+//be rewritten from what `recording::screen` returns.
+
+#[test]
+fn placing_a_card() {
+    let size = Size::new(80, 30);
+    recording::reset(size);
+    let platform = recording::platform();
+    let mut game = game::new_game([Instruction::NOP; PLAYFIELD_SIZE], size, 42, 42);
+
+    let card = game.cards[0].location;
+    let playfield = game.layout.playfield.top_left;
+
+    let mut frames = recording::wait(1);
+    frames.extend(recording::click(card.x + 1, card.y + 1));
+    frames.extend(recording::click(playfield.x + 2, playfield.y + 3));
+    frames.extend(recording::wait(1));
+    let quit = recording::play(game::update_and_render, &platform, &mut game, frames);
+
+    assert!(!quit);
+    assert_eq!(recording::screen(), include_str!("fixtures/placing_a_card.txt").trim_end());
+}
@@ -0,0 +1,26 @@
+extern crate common;
+extern crate game;
+
+use common::*;
+
+fn new_game() -> Game {
+    game::new_game(get_instructions(), Size::new(80, 30), DEFAULT_SEED, DEFAULT_SEED)
+}
+
+#[test]
+fn loads_what_was_saved() {
+    let text = save::save(&new_game());
+
+    assert!(save::load(&text, new_game()).is_ok());
+}
+
+#[test]
+fn rejects_other_format_versions() {
+    let text = save::save(&new_game());
+    let (header, rest) = text.split_at(text.find('\n').unwrap());
+    let newer = header.replace(&save::FORMAT_VERSION.to_string(),
+                               &(save::FORMAT_VERSION + 1).to_string()) + rest;
+    assert!(newer != text);
+
+    assert!(save::load(&newer, new_game()).is_err());
+}
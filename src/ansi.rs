@@ -0,0 +1,608 @@
+//A backend that draws with ANSI escape codes in whatever terminal the program
+//was started from, so BearLibTerminal doesn't need to be installed.
+//
+//`Platform` is a table of plain function pointers, so the state they share
+//lives in a thread local. Drawing goes into a grid of cells and `refresh`
+//only writes the cells that changed since the last refresh, which keeps the
+//amount of output reasonable over slow connections.
+
+use std::cell::RefCell;
+use std::io::{self, Read, Write};
+use std::mem;
+use std::process::{Command, Stdio};
+use std::sync::mpsc::{self, Receiver};
+use std::thread;
+use std::time::{Duration, Instant};
+
+use common::*;
+
+use {services, Backend};
+
+pub fn backend() -> Backend {
+    Backend {
+        platform: Platform {
+            print_xy,
+            clear,
+            size,
+            mouse_position,
+            clicks,
+            key_pressed,
+            set_colors,
+            time: clock::time,
+            read_blob: services::read_blob,
+            write_blob: services::write_blob,
+            log: services::log,
+        },
+        open,
+        read_events,
+        clear_screen,
+        refresh,
+        close,
+    }
+}
+
+type Rgb = (u8, u8, u8);
+
+const DEFAULT_FG: Rgb = (255, 255, 255);
+const DEFAULT_BG: Rgb = (0, 0, 0);
+
+//how often to ask the terminal for its size, since there is no resize event
+const SIZE_POLL_INTERVAL_MS: u64 = 500;
+
+#[derive(Clone, Copy, PartialEq)]
+struct Cell {
+    character: char,
+    fg: Rgb,
+    bg: Rgb,
+}
+
+const BLANK: Cell = Cell {
+    character: ' ',
+    fg: DEFAULT_FG,
+    bg: DEFAULT_BG,
+};
+
+struct State {
+    size: Size,
+    back: Vec<Cell>,
+    //what is currently on the terminal. `None` means it must be redrawn fully.
+    front: Option<Vec<Cell>>,
+    fg: Rgb,
+    bg: Rgb,
+    mouse_position: Point,
+    mouse_buttons: [bool; 3],
+    input: Option<Receiver<Vec<u8>>>,
+    //the start of an escape sequence that the last read cut off, which goes
+    //in front of the next read.
+    pending: Vec<u8>,
+    saved_stty: Option<String>,
+    last_size_check: Instant,
+}
+
+impl State {
+    fn new() -> Self {
+        State {
+            size: Size::new(0, 0),
+            back: Vec::new(),
+            front: None,
+            fg: DEFAULT_FG,
+            bg: DEFAULT_BG,
+            mouse_position: Point::new(0, 0),
+            mouse_buttons: [false; 3],
+            input: None,
+            pending: Vec::new(),
+            saved_stty: None,
+            last_size_check: Instant::now(),
+        }
+    }
+
+    fn resize(&mut self, size: Size) {
+        self.size = size;
+        self.back = vec![BLANK; (size.width * size.height) as usize];
+        self.front = None;
+    }
+
+    fn cell_mut(&mut self, x: i32, y: i32) -> Option<&mut Cell> {
+        if x < 0 || y < 0 || x >= self.size.width || y >= self.size.height {
+            None
+        } else {
+            let index = (y * self.size.width + x) as usize;
+            self.back.get_mut(index)
+        }
+    }
+}
+
+thread_local! {
+    static STATE: RefCell<State> = RefCell::new(State::new());
+}
+
+fn stty(args: &[&str]) -> Option<String> {
+    Command::new("stty")
+        .args(args)
+        .stdin(Stdio::inherit())
+        .output()
+        .ok()
+        .and_then(|output| if output.status.success() {
+            String::from_utf8(output.stdout).ok()
+        } else {
+            None
+        })
+}
+
+fn terminal_size() -> Option<Size> {
+    stty(&["size"]).and_then(|text| {
+        let mut parts = text.split_whitespace().filter_map(|part| part.parse::<i32>().ok());
+
+        match (parts.next(), parts.next()) {
+            (Some(height), Some(width)) if width > 0 && height > 0 => {
+                Some(Size::new(width, height))
+            }
+            _ => None,
+        }
+    })
+}
+
+fn open(_title: &str, width: i32, height: i32) {
+    let saved_stty = stty(&["-g"]).map(|text| text.trim().to_string());
+    stty(&["raw", "-echo"]);
+
+    //alternate screen, hidden cursor, and mouse reporting for buttons and
+    //motion, in the SGR format so coordinates past 223 work.
+    print!("\x1b[?1049h\x1b[?25l\x1b[?1003h\x1b[?1006h");
+    let _ = io::stdout().flush();
+
+    let (sender, receiver) = mpsc::channel();
+    thread::spawn(move || {
+        let stdin = io::stdin();
+        let mut buffer = [0; 256];
+        loop {
+            match stdin.lock().read(&mut buffer) {
+                Ok(0) | Err(_) => break,
+                Ok(count) => {
+                    if sender.send(buffer[..count].to_vec()).is_err() {
+                        break;
+                    }
+                }
+            }
+        }
+    });
+
+    let size = terminal_size().unwrap_or_else(|| Size::new(width, height));
+
+    STATE.with(|state| {
+        let mut state = state.borrow_mut();
+        state.resize(size);
+        state.input = Some(receiver);
+        state.saved_stty = saved_stty;
+    });
+}
+
+fn close() {
+    print!("\x1b[0m\x1b[?1006l\x1b[?1003l\x1b[?25h\x1b[?1049l");
+    let _ = io::stdout().flush();
+
+    let saved_stty = STATE.with(|state| state.borrow_mut().saved_stty.take());
+    match saved_stty {
+        Some(settings) => stty(&[&settings]),
+        None => stty(&["sane"]),
+    };
+}
+
+fn read_events(events: &mut Vec<Event>) {
+    STATE.with(|state| {
+        let mut state = state.borrow_mut();
+
+        if state.last_size_check.elapsed() >= Duration::from_millis(SIZE_POLL_INTERVAL_MS) {
+            state.last_size_check = Instant::now();
+
+            if let Some(size) = terminal_size() {
+                if size != state.size {
+                    state.resize(size);
+                    events.push(Event::Resize {
+                        width: size.width,
+                        height: size.height,
+                    });
+                }
+            }
+        }
+
+        let mut chunks = Vec::new();
+        if let Some(ref input) = state.input {
+            while let Ok(chunk) = input.try_recv() {
+                chunks.push(chunk);
+            }
+        }
+
+        for chunk in chunks {
+            parse_input(&mut state, &chunk, events);
+        }
+    });
+}
+
+fn parse_input(state: &mut State, chunk: &[u8], events: &mut Vec<Event>) {
+    let mut bytes = mem::take(&mut state.pending);
+    bytes.extend_from_slice(chunk);
+
+    let mut i = 0;
+    while i < bytes.len() {
+        if bytes[i] == 0x1b && i + 1 < bytes.len() && (bytes[i + 1] == b'[' || bytes[i + 1] == b'O') {
+            //find the end of the escape sequence
+            let mut end = i + 2;
+            while end < bytes.len() && !(bytes[end] >= 0x40 && bytes[end] <= 0x7e) {
+                end += 1;
+            }
+            if end >= bytes.len() {
+                state.pending = bytes[i..].to_vec();
+                break;
+            }
+
+            parse_escape_sequence(state, &bytes[i + 1..end + 1], events);
+            i = end + 1;
+            continue;
+        }
+
+        let (key, ctrl, shift) = match bytes[i] {
+            0x1b => (Some(KeyCode::Escape), false, false),
+            b'\r' | b'\n' => (Some(KeyCode::Enter), false, false),
+            b'\t' => (Some(KeyCode::Tab), false, false),
+            0x7f | 0x08 => (Some(KeyCode::Backspace), false, false),
+            byte @ 1..=26 => (letter_key(byte - 1 + b'a'), true, false),
+            byte @ b'a'..=b'z' => (letter_key(byte), false, false),
+            byte @ b'A'..=b'Z' => (letter_key(byte - b'A' + b'a'), false, true),
+            byte => {
+                match character_key(byte) {
+                    Some((key, shift)) => (Some(key), false, shift),
+                    None => (None, false, false),
+                }
+            }
+        };
+
+        if let Some(key) = key {
+            events.push(Event::KeyPressed {
+                key,
+                ctrl,
+                shift,
+            });
+        }
+
+        i += 1;
+    }
+}
+
+fn parse_escape_sequence(state: &mut State, sequence: &[u8], events: &mut Vec<Event>) {
+    let text = String::from_utf8_lossy(sequence).into_owned();
+
+    //SGR mouse reports look like `[<button;x;yM`, or end in `m` on release.
+    if text.starts_with("[<") {
+        let released = text.ends_with('m');
+        let numbers: Vec<i32> = text[2..text.len() - 1]
+            .split(';')
+            .filter_map(|part| part.parse().ok())
+            .collect();
+
+        if numbers.len() == 3 {
+            let button = numbers[0];
+            let position = Point::new_safe(numbers[1] - 1, numbers[2] - 1);
+            let ctrl = button & 16 != 0;
+            let shift = button & 4 != 0;
+
+            if position != state.mouse_position {
+                state.mouse_position = position;
+                events.push(Event::MouseMove {
+                    x: position.x,
+                    y: position.y,
+                });
+            }
+
+            if button & 64 != 0 {
+                events.push(Event::MouseScroll { delta: if button & 1 == 0 { -1 } else { 1 } });
+            } else if button & 32 == 0 {
+                let index = (button & 3) as usize;
+                let key = match index {
+                    0 => KeyCode::MouseLeft,
+                    1 => KeyCode::MouseMiddle,
+                    2 => KeyCode::MouseRight,
+                    _ => return,
+                };
+
+                state.mouse_buttons[index] = !released;
+                events.push(if released {
+                    Event::KeyReleased {
+                        key,
+                        ctrl,
+                        shift,
+                    }
+                } else {
+                    Event::KeyPressed {
+                        key,
+                        ctrl,
+                        shift,
+                    }
+                });
+            }
+        }
+
+        return;
+    }
+
+    //other sequences look like `[A`, `[1;5A`, `[3~`, `[15;2~` or `OP`. The
+    //second number, if present, is one more than a bitmask of the modifiers.
+    let final_byte = sequence[sequence.len() - 1];
+    let numbers: Vec<i32> = text[1..text.len() - 1]
+        .split(';')
+        .filter_map(|part| part.parse().ok())
+        .collect();
+    let modifiers = numbers.get(1).map_or(0, |m| m - 1);
+    let shift = modifiers & 1 != 0;
+    let ctrl = modifiers & 4 != 0;
+
+    let key = match (final_byte, numbers.first().cloned()) {
+        (b'A', _) => KeyCode::Up,
+        (b'B', _) => KeyCode::Down,
+        (b'C', _) => KeyCode::Right,
+        (b'D', _) => KeyCode::Left,
+        (b'H', _) => KeyCode::Home,
+        (b'F', _) => KeyCode::End,
+        (b'P', _) => KeyCode::F1,
+        (b'Q', _) => KeyCode::F2,
+        (b'R', _) => KeyCode::F3,
+        (b'S', _) => KeyCode::F4,
+        (b'Z', _) => {
+            events.push(Event::KeyPressed {
+                key: KeyCode::Tab,
+                ctrl: false,
+                shift: true,
+            });
+            return;
+        }
+        (b'~', Some(number)) => {
+            match number {
+                1 | 7 => KeyCode::Home,
+                2 => KeyCode::Insert,
+                3 => KeyCode::Delete,
+                4 | 8 => KeyCode::End,
+                5 => KeyCode::PageUp,
+                6 => KeyCode::PageDown,
+                15 => KeyCode::F5,
+                17 => KeyCode::F6,
+                18 => KeyCode::F7,
+                19 => KeyCode::F8,
+                20 => KeyCode::F9,
+                21 => KeyCode::F10,
+                23 => KeyCode::F11,
+                24 => KeyCode::F12,
+                _ => return,
+            }
+        }
+        _ => return,
+    };
+
+    events.push(Event::KeyPressed {
+        key,
+        ctrl,
+        shift,
+    });
+}
+
+fn letter_key(byte: u8) -> Option<KeyCode> {
+    let keys = [KeyCode::A, KeyCode::B, KeyCode::C, KeyCode::D, KeyCode::E, KeyCode::F,
+                KeyCode::G, KeyCode::H, KeyCode::I, KeyCode::J, KeyCode::K, KeyCode::L,
+                KeyCode::M, KeyCode::N, KeyCode::O, KeyCode::P, KeyCode::Q, KeyCode::R,
+                KeyCode::S, KeyCode::T, KeyCode::U, KeyCode::V, KeyCode::W, KeyCode::X,
+                KeyCode::Y, KeyCode::Z];
+
+    keys.get(byte.wrapping_sub(b'a') as usize).cloned()
+}
+
+//returns the key and whether shift is needed to type the character on a US
+//layout.
+fn character_key(byte: u8) -> Option<(KeyCode, bool)> {
+    let result = match byte {
+        b'1' => (KeyCode::Row1, false),
+        b'2' => (KeyCode::Row2, false),
+        b'3' => (KeyCode::Row3, false),
+        b'4' => (KeyCode::Row4, false),
+        b'5' => (KeyCode::Row5, false),
+        b'6' => (KeyCode::Row6, false),
+        b'7' => (KeyCode::Row7, false),
+        b'8' => (KeyCode::Row8, false),
+        b'9' => (KeyCode::Row9, false),
+        b'0' => (KeyCode::Row0, false),
+        b'!' => (KeyCode::Row1, true),
+        b'@' => (KeyCode::Row2, true),
+        b'#' => (KeyCode::Row3, true),
+        b'$' => (KeyCode::Row4, true),
+        b'%' => (KeyCode::Row5, true),
+        b'^' => (KeyCode::Row6, true),
+        b'&' => (KeyCode::Row7, true),
+        b'*' => (KeyCode::Row8, true),
+        b'(' => (KeyCode::Row9, true),
+        b')' => (KeyCode::Row0, true),
+        b' ' => (KeyCode::Space, false),
+        b'`' => (KeyCode::Grave, false),
+        b'~' => (KeyCode::Grave, true),
+        b'-' => (KeyCode::Minus, false),
+        b'_' => (KeyCode::Minus, true),
+        b'=' => (KeyCode::Equals, false),
+        b'+' => (KeyCode::Equals, true),
+        b'[' => (KeyCode::LeftBracket, false),
+        b'{' => (KeyCode::LeftBracket, true),
+        b']' => (KeyCode::RightBracket, false),
+        b'}' => (KeyCode::RightBracket, true),
+        b'\\' => (KeyCode::Backslash, false),
+        b'|' => (KeyCode::Backslash, true),
+        b';' => (KeyCode::Semicolon, false),
+        b':' => (KeyCode::Semicolon, true),
+        b'\'' => (KeyCode::Apostrophe, false),
+        b'"' => (KeyCode::Apostrophe, true),
+        b',' => (KeyCode::Comma, false),
+        b'<' => (KeyCode::Comma, true),
+        b'.' => (KeyCode::Period, false),
+        b'>' => (KeyCode::Period, true),
+        b'/' => (KeyCode::Slash, false),
+        b'?' => (KeyCode::Slash, true),
+        _ => return None,
+    };
+
+    Some(result)
+}
+
+fn print_xy(x: i32, y: i32, text: &str) {
+    STATE.with(|state| {
+        let mut state = state.borrow_mut();
+        let (fg, bg) = (state.fg, state.bg);
+
+        for (i, character) in text.chars().enumerate() {
+            if let Some(cell) = state.cell_mut(x + i as i32, y) {
+                *cell = Cell {
+                    character,
+                    fg,
+                    bg,
+                };
+            }
+        }
+    });
+}
+
+fn clear(area: Option<Rect>) {
+    STATE.with(|state| {
+        let mut state = state.borrow_mut();
+        let bg = state.bg;
+
+        let (x, y, width, height) = match area {
+            Some(rect) => (rect.top_left.x, rect.top_left.y, rect.size.width, rect.size.height),
+            None => (0, 0, state.size.width, state.size.height),
+        };
+
+        for cell_y in y..y + height {
+            for cell_x in x..x + width {
+                if let Some(cell) = state.cell_mut(cell_x, cell_y) {
+                    *cell = Cell { bg, ..BLANK };
+                }
+            }
+        }
+    });
+}
+
+fn clear_screen() {
+    STATE.with(|state| {
+        for cell in state.borrow_mut().back.iter_mut() {
+            *cell = BLANK;
+        }
+    });
+}
+
+fn size() -> Size {
+    STATE.with(|state| state.borrow().size)
+}
+
+fn mouse_position() -> Point {
+    STATE.with(|state| state.borrow().mouse_position)
+}
+
+fn clicks() -> i32 {
+    if key_pressed(KeyCode::MouseLeft) { 1 } else { 0 }
+}
+
+//terminals only report presses for the keyboard, so only mouse buttons can be
+//seen as held down.
+fn key_pressed(key: KeyCode) -> bool {
+    STATE.with(|state| {
+        let state = state.borrow();
+        match key {
+            KeyCode::MouseLeft => state.mouse_buttons[0],
+            KeyCode::MouseMiddle => state.mouse_buttons[1],
+            KeyCode::MouseRight => state.mouse_buttons[2],
+            _ => false,
+        }
+    })
+}
+
+fn set_colors(fg: Color, bg: Color) {
+    STATE.with(|state| {
+        let mut state = state.borrow_mut();
+        state.fg = (fg.red, fg.green, fg.blue);
+        state.bg = (bg.red, bg.green, bg.blue);
+    });
+}
+
+fn refresh() {
+    let mut output = String::new();
+
+    STATE.with(|state| {
+        let mut state = state.borrow_mut();
+        let width = state.size.width;
+
+        let mut colors = None;
+        //the position the terminal's cursor will be at after the last write
+        let mut cursor = None;
+
+        {
+            let front = state.front.as_ref();
+            for (index, cell) in state.back.iter().enumerate() {
+                if let Some(front) = front {
+                    if front.get(index) == Some(cell) {
+                        continue;
+                    }
+                }
+
+                let (x, y) = (index as i32 % width, index as i32 / width);
+
+                if cursor != Some((x, y)) {
+                    output.push_str(&format!("\x1b[{};{}H", y + 1, x + 1));
+                }
+                if colors != Some((cell.fg, cell.bg)) {
+                    output.push_str(&format!("\x1b[38;2;{};{};{}m\x1b[48;2;{};{};{}m",
+                                             cell.fg.0,
+                                             cell.fg.1,
+                                             cell.fg.2,
+                                             cell.bg.0,
+                                             cell.bg.1,
+                                             cell.bg.2));
+                    colors = Some((cell.fg, cell.bg));
+                }
+
+                output.push(cell.character);
+                cursor = Some((x + 1, y));
+            }
+        }
+
+        state.front = Some(state.back.clone());
+    });
+
+    if !output.is_empty() {
+        let stdout = io::stdout();
+        let mut stdout = stdout.lock();
+        let _ = stdout.write_all(output.as_bytes());
+        let _ = stdout.flush();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use common::*;
+
+    use super::{parse_input, State};
+
+    #[test]
+    fn escape_sequence_split_across_reads() {
+        let mut state = State::new();
+        let mut events = Vec::new();
+
+        parse_input(&mut state, b"a\x1b[<0;5", &mut events);
+        parse_input(&mut state, b";3M", &mut events);
+
+        assert_eq!(events,
+                   vec![Event::KeyPressed {
+                            key: KeyCode::A,
+                            ctrl: false,
+                            shift: false,
+                        },
+                        Event::MouseMove { x: 4, y: 2 },
+                        Event::KeyPressed {
+                            key: KeyCode::MouseLeft,
+                            ctrl: false,
+                            shift: false,
+                        }]);
+    }
+}
@@ -0,0 +1,300 @@
+use bear_lib_terminal::terminal::{self, config, state};
+use bear_lib_terminal::Color;
+use bear_lib_terminal::geometry::{Point, Rect, Size};
+
+use common::*;
+
+use {services, Backend};
+
+pub fn backend() -> Backend {
+    Backend {
+        platform: Platform {
+            print_xy: terminal::print_xy,
+            clear,
+            size,
+            mouse_position,
+            clicks: terminal::state::mouse::clicks,
+            key_pressed,
+            set_colors,
+            time: clock::time,
+            read_blob: services::read_blob,
+            write_blob: services::write_blob,
+            log: services::log,
+        },
+        open,
+        read_events,
+        clear_screen,
+        refresh: terminal::refresh,
+        close: terminal::close,
+    }
+}
+
+fn open(title: &str, width: i32, height: i32) {
+    terminal::open(title, width as u32, height as u32);
+    terminal::set(config::Window::empty().resizeable(true));
+    terminal::set(vec![config::InputFilter::Group {
+                           group: config::InputFilterGroup::Keyboard,
+                           both: false,
+                       },
+                       config::InputFilter::Group {
+                           group: config::InputFilterGroup::Mouse,
+                           both: false,
+                       }]);
+}
+
+fn read_events(events: &mut Vec<Event>) {
+    while let Some(event) = terminal::read_event() {
+        events.push(from_terminal_event(event));
+    }
+}
+
+fn clear_screen() {
+    terminal::clear(None);
+}
+
+fn clear(area: Option<common::Rect>) {
+    terminal::clear(area.map(to_terminal_rect));
+}
+
+fn size() -> common::Size {
+    from_terminal_size(state::size())
+}
+
+fn mouse_position() -> common::Point {
+    from_terminal_point(state::mouse::position())
+}
+
+fn key_pressed(key: common::KeyCode) -> bool {
+    terminal::state::key_pressed(to_terminal_key(key))
+}
+
+fn set_colors(fg: common::Color, bg: common::Color) {
+    terminal::set_colors(to_terminal_color(fg), to_terminal_color(bg));
+}
+
+//`common` declares its own copies of BearLibTerminal's types so the `game`
+//crate doesn't depend on it. The conversions are spelled out field by field and
+//variant by variant, with no wildcards, so if a BearLibTerminal upgrade adds
+//or changes anything, this stops compiling rather than misreading input.
+
+fn from_terminal_point(point: Point) -> common::Point {
+    common::Point {
+        x: point.x,
+        y: point.y,
+    }
+}
+
+fn to_terminal_point(point: common::Point) -> Point {
+    Point {
+        x: point.x,
+        y: point.y,
+    }
+}
+
+fn from_terminal_size(size: Size) -> common::Size {
+    common::Size {
+        width: size.width,
+        height: size.height,
+    }
+}
+
+fn to_terminal_size(size: common::Size) -> Size {
+    Size {
+        width: size.width,
+        height: size.height,
+    }
+}
+
+fn to_terminal_rect(rect: common::Rect) -> Rect {
+    let common::Rect { top_left, top_right, bottom_right, bottom_left, size } = rect;
+
+    Rect {
+        top_left: to_terminal_point(top_left),
+        top_right: to_terminal_point(top_right),
+        bottom_right: to_terminal_point(bottom_right),
+        bottom_left: to_terminal_point(bottom_left),
+        size: to_terminal_size(size),
+    }
+}
+
+fn to_terminal_color(color: common::Color) -> Color {
+    let common::Color { red, green, blue, alpha } = color;
+
+    Color {
+        red,
+        green,
+        blue,
+        alpha,
+    }
+}
+
+//the two directions are generated from one list, so a key missing from either
+//enum, or from the list, makes one of the matches fail to compile. The tests
+//go through the same list.
+macro_rules! key_conversions {
+    ($($key:ident),* $(,)*) => {
+        fn from_terminal_key(key: terminal::KeyCode) -> common::KeyCode {
+            match key {
+                $(terminal::KeyCode::$key => common::KeyCode::$key,)*
+            }
+        }
+
+        fn to_terminal_key(key: common::KeyCode) -> terminal::KeyCode {
+            match key {
+                $(common::KeyCode::$key => terminal::KeyCode::$key,)*
+            }
+        }
+
+        #[cfg(test)]
+        const TERMINAL_KEY_CODES: &[terminal::KeyCode] = &[$(terminal::KeyCode::$key),*];
+    }
+}
+
+key_conversions!(
+    A, B, C, D, E, F, G, H, I, J, K, L, M, N, O, P, Q, R, S, T, U, V, W, X, Y, Z, Row1, Row2, Row3,
+    Row4, Row5, Row6, Row7, Row8, Row9, Row0, Grave, Minus, Equals, LeftBracket, RightBracket,
+    Backslash, Semicolon, Apostrophe, Comma, Period, Slash, F1, F2, F3, F4, F5, F6, F7, F8, F9,
+    F10, F11, F12, Enter, Escape, Backspace, Tab, Space, Pause, Insert, Home, PageUp, Delete, End,
+    PageDown, Right, Left, Down, Up, NumDivide, NumMultiply, NumMinus, NumPlus, NumEnter,
+    NumPeriod, Num1, Num2, Num3, Num4, Num5, Num6, Num7, Num8, Num9, Num0, MouseLeft, MouseRight,
+    MouseMiddle, MouseFourth, MouseFifth,
+);
+
+fn from_terminal_event(event: terminal::Event) -> Event {
+    match event {
+        terminal::Event::Close => Event::Close,
+        terminal::Event::Resize { width, height } => {
+            Event::Resize {
+                width,
+                height,
+            }
+        }
+        terminal::Event::MouseMove { x, y } => Event::MouseMove { x, y },
+        terminal::Event::MouseScroll { delta } => Event::MouseScroll { delta },
+        terminal::Event::KeyPressed { key, ctrl, shift } => {
+            Event::KeyPressed {
+                key: from_terminal_key(key),
+                ctrl,
+                shift,
+            }
+        }
+        terminal::Event::KeyReleased { key, ctrl, shift } => {
+            Event::KeyReleased {
+                key: from_terminal_key(key),
+                ctrl,
+                shift,
+            }
+        }
+        terminal::Event::ShiftPressed => Event::ShiftPressed,
+        terminal::Event::ShiftReleased => Event::ShiftReleased,
+        terminal::Event::ControlPressed => Event::ControlPressed,
+        terminal::Event::ControlReleased => Event::ControlReleased,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use bear_lib_terminal::terminal;
+
+    use common::*;
+
+    use super::{from_terminal_event, from_terminal_key, to_terminal_key, TERMINAL_KEY_CODES};
+
+    //the other direction to `from_terminal_event`, which only the tests need.
+    //Like the conversions above it has no wildcards, so an event added to
+    //either side without the other fails to compile.
+    fn to_terminal_event(event: Event) -> terminal::Event {
+        match event {
+            Event::Close => terminal::Event::Close,
+            Event::Resize { width, height } => terminal::Event::Resize { width, height },
+            Event::MouseMove { x, y } => terminal::Event::MouseMove { x, y },
+            Event::MouseScroll { delta } => terminal::Event::MouseScroll { delta },
+            Event::KeyPressed { key, ctrl, shift } => {
+                terminal::Event::KeyPressed {
+                    key: to_terminal_key(key),
+                    ctrl,
+                    shift,
+                }
+            }
+            Event::KeyReleased { key, ctrl, shift } => {
+                terminal::Event::KeyReleased {
+                    key: to_terminal_key(key),
+                    ctrl,
+                    shift,
+                }
+            }
+            Event::ShiftPressed => terminal::Event::ShiftPressed,
+            Event::ShiftReleased => terminal::Event::ShiftReleased,
+            Event::ControlPressed => terminal::Event::ControlPressed,
+            Event::ControlReleased => terminal::Event::ControlReleased,
+        }
+    }
+
+    //which kind of event `event` is, so the tests can check they have one of
+    //every kind.
+    fn kind(event: Event) -> usize {
+        match event {
+            Event::Close => 0,
+            Event::Resize { .. } => 1,
+            Event::MouseMove { .. } => 2,
+            Event::MouseScroll { .. } => 3,
+            Event::KeyPressed { .. } => 4,
+            Event::KeyReleased { .. } => 5,
+            Event::ShiftPressed => 6,
+            Event::ShiftReleased => 7,
+            Event::ControlPressed => 8,
+            Event::ControlReleased => 9,
+        }
+    }
+    const KIND_COUNT: usize = 10;
+
+    fn every_event() -> Vec<Event> {
+        let mut events = vec![Event::Close,
+                              Event::Resize {
+                                  width: 80,
+                                  height: 30,
+                              },
+                              Event::MouseMove { x: 3, y: 4 },
+                              Event::MouseScroll { delta: -2 },
+                              Event::ShiftPressed,
+                              Event::ShiftReleased,
+                              Event::ControlPressed,
+                              Event::ControlReleased];
+
+        for &key in ALL_KEY_CODES.iter() {
+            for &(ctrl, shift) in [(false, false), (true, false), (false, true)].iter() {
+                events.push(Event::KeyPressed { key, ctrl, shift });
+                events.push(Event::KeyReleased { key, ctrl, shift });
+            }
+        }
+
+        events
+    }
+
+    #[test]
+    fn every_key_round_trips() {
+        for &key in ALL_KEY_CODES.iter() {
+            assert_eq!(from_terminal_key(to_terminal_key(key)), key);
+        }
+        for &key in TERMINAL_KEY_CODES.iter() {
+            assert_eq!(to_terminal_key(from_terminal_key(key)), key);
+        }
+
+        assert_eq!(TERMINAL_KEY_CODES.len(), ALL_KEY_CODES.len());
+    }
+
+    #[test]
+    fn every_event_round_trips() {
+        let events = every_event();
+
+        for kind_index in 0..KIND_COUNT {
+            assert!(events.iter().any(|&event| kind(event) == kind_index),
+                    "no event of kind {}",
+                    kind_index);
+        }
+
+        for event in events {
+            assert_eq!(from_terminal_event(to_terminal_event(event)), event);
+        }
+    }
+}
@@ -0,0 +1,602 @@
+//Runs the VM without a window, so playfields can be checked in environments
+//where BearLibTerminal isn't installed. This binary must not use
+//`bear_lib_terminal`, otherwise it would need `libBearLibTerminal.so` to link.
+
+extern crate common;
+extern crate game;
+
+use common::*;
+use common::theme::Theme;
+
+use std::fs::{self, File};
+use std::io::Read;
+use std::process;
+use std::time::{Duration, SystemTime};
+
+const USAGE: &str = "usage: headless [--playfield FILE] [--script FILE] [--steps N] [--json]
+                [--seed N] [--replay FILE] [--screen] [--theme FILE] [--profile]
+                [--layers] [--analyse] [--data DIR]
+
+  --playfield FILE  a playfield with one instruction per line, like `load 0x02 A`,
+                    or a game saved by the windowed game, which the
+                    playfield is taken from. Defaults to the built-in
+                    playfield.
+  --script FILE     card placements to apply before running, one per line, in
+                    the form `place CARD_INDEX ADDRESS`, or `scrape ADDRESS` to
+                    uncover what was there before. Cards are dealt the same
+                    way as in the game.
+  --steps N         the most instructions to execute. Defaults to 10000.
+  --json            print the results as JSON instead of text.
+  --seed N          the seed cards are dealt with. Defaults to 42.
+  --replay FILE     instead of running the VM directly, play back a replay
+                    recorded with `--record` in the windowed game, then report
+                    the state the game ended up in and anything it logged. The
+                    replay's own seed is used instead of `--seed`. The game
+                    reads back what it read while recording, and otherwise
+                    storage starts out empty, or as `--data` says.
+  --screen          also print what was on screen at the end of the replay.
+  --theme FILE      draw the replay with the theme in FILE, in the format the
+                    game reads `theme.txt` in. Only `boxes` changes what
+                    `--screen` prints.
+  --profile         also report how many times each address was executed, and
+                    how many steps were spent in each loop.
+  --layers          also report every instruction that was written over, for
+                    each address that has any, newest first, along with the
+                    card, player, turn and time that placed each one.
+  --analyse         also report what can be worked out without running the
+                    playfield: the addresses execution can't reach, where it
+                    can leave the playfield, and loops it can never leave.
+  --data DIR        start storage out with the files in DIR, like the
+                    `palimpsest-data` directory the windowed game keeps its
+                    blobs in, so a replay sees what the window did. Nothing
+                    is written back to DIR.";
+
+const DEFAULT_STEP_LIMIT: u64 = 10000;
+
+//the size the window opens at, which decides where cards are dealt.
+const WINDOW_SIZE: Size = Size {
+    width: 80,
+    height: 30,
+};
+
+struct Options {
+    playfield_path: Option<String>,
+    script_path: Option<String>,
+    step_limit: u64,
+    json: bool,
+    seed: Seed,
+    replay_path: Option<String>,
+    screen: bool,
+    theme_path: Option<String>,
+    profile: bool,
+    layers: bool,
+    analyse: bool,
+    data_path: Option<String>,
+}
+
+enum Termination {
+    Halted,
+    StepLimit,
+    ReplayEnded,
+    Quit,
+}
+
+impl Termination {
+    fn name(&self) -> &'static str {
+        match *self {
+            Termination::Halted => "halted",
+            Termination::StepLimit => "step-limit",
+            Termination::ReplayEnded => "replay-ended",
+            Termination::Quit => "quit",
+        }
+    }
+}
+
+struct Report {
+    //what `count` is counting, either VM steps or replayed frames.
+    count_name: &'static str,
+    count: u64,
+    termination: Termination,
+    screen: Option<String>,
+    //what the game logged through `Platform::log`.
+    log: Vec<String>,
+}
+
+fn main() {
+    let options = parse_args().unwrap_or_else(|error| fail(&error));
+
+    let instructions = match options.playfield_path {
+        Some(ref path) => {
+            load_playfield(&read_file(path)).unwrap_or_else(|error| {
+                fail(&format!("{}: {}", path, error))
+            })
+        }
+        None => get_instructions(),
+    };
+
+    let (report, game) = match options.replay_path {
+        Some(ref path) => {
+            let replay = replay::parse(&read_file(path))
+                .unwrap_or_else(|error| fail(&format!("{}: {}", path, error)));
+
+            let theme = match options.theme_path {
+                Some(ref path) => {
+                    let (theme, problems) = Theme::parse(&read_file(path));
+                    if let Some(problem) = problems.first() {
+                        fail(&format!("{}: {}", path, problem));
+                    }
+
+                    theme
+                }
+                None => Theme::default(),
+            };
+
+            let blobs = match options.data_path {
+                Some(ref path) => {
+                    read_blobs(path).unwrap_or_else(|error| fail(&format!("{}: {}", path, error)))
+                }
+                None => Vec::new(),
+            };
+
+            play_replay(instructions, replay, theme, &blobs, options.screen)
+        }
+        None => {
+            let mut game = game::new_game(instructions,
+                                          WINDOW_SIZE,
+                                          options.seed,
+                                          daily_seed(SystemTime::now()));
+
+            if let Some(ref path) = options.script_path {
+                apply_script(&mut game, &read_file(path))
+                    .unwrap_or_else(|error| fail(&format!("{}: {}", path, error)));
+            }
+
+            let (steps, termination) = run(&mut game, options.step_limit);
+
+            (Report {
+                count_name: "steps",
+                count: steps,
+                termination,
+                screen: None,
+                log: Vec::new(),
+            },
+             game)
+        }
+    };
+
+    if options.json {
+        print_json(&game, &report, &options);
+    } else {
+        print_text(&game, &report, &options);
+    }
+}
+
+fn parse_args() -> Result<Options, String> {
+    let mut options = Options {
+        playfield_path: None,
+        script_path: None,
+        step_limit: DEFAULT_STEP_LIMIT,
+        json: false,
+        seed: DEFAULT_SEED,
+        replay_path: None,
+        screen: false,
+        theme_path: None,
+        profile: false,
+        layers: false,
+        analyse: false,
+        data_path: None,
+    };
+
+    let mut args = std::env::args().skip(1);
+    while let Some(arg) = args.next() {
+        match arg.as_ref() {
+            "--playfield" => options.playfield_path = Some(next_value(&mut args, &arg)?),
+            "--script" => options.script_path = Some(next_value(&mut args, &arg)?),
+            "--steps" => {
+                let value = next_value(&mut args, &arg)?;
+                options.step_limit = value.parse()
+                    .map_err(|_| format!("\"{}\" is not a step count", value))?;
+            }
+            "--json" => options.json = true,
+            "--seed" => {
+                let value = next_value(&mut args, &arg)?;
+                options.seed = value.parse().map_err(|_| format!("\"{}\" is not a seed", value))?;
+            }
+            "--replay" => options.replay_path = Some(next_value(&mut args, &arg)?),
+            "--screen" => options.screen = true,
+            "--theme" => options.theme_path = Some(next_value(&mut args, &arg)?),
+            "--profile" => options.profile = true,
+            "--layers" => options.layers = true,
+            "--analyse" => options.analyse = true,
+            "--data" => options.data_path = Some(next_value(&mut args, &arg)?),
+            "--help" | "-h" => {
+                println!("{}", USAGE);
+                process::exit(0);
+            }
+            other => return Err(format!("unknown argument \"{}\"", other)),
+        }
+    }
+
+    Ok(options)
+}
+
+//`text` is either a saved game or a playfield in the text format.
+fn load_playfield(text: &str) -> Result<[Instruction; PLAYFIELD_SIZE], String> {
+    if save::is_save(text) {
+        let defaults = game::new_game(get_instructions(), WINDOW_SIZE, DEFAULT_SEED, DEFAULT_SEED);
+
+        save::load(text, defaults).map(|game| game.instructions)
+    } else {
+        parse_playfield(text)
+    }
+}
+
+fn next_value<I: Iterator<Item = String>>(args: &mut I, flag: &str) -> Result<String, String> {
+    args.next().ok_or_else(|| format!("{} needs a value", flag))
+}
+
+fn read_file(path: &str) -> String {
+    let mut text = String::new();
+
+    File::open(path)
+        .and_then(|mut file| file.read_to_string(&mut text))
+        .unwrap_or_else(|error| fail(&format!("{}: {}", path, error)));
+
+    text
+}
+
+//every file in `path` whose name can be a blob's, by name. Anything else, like
+//a directory, is left out, since the windowed game couldn't read it either.
+fn read_blobs(path: &str) -> Result<Vec<(String, Vec<u8>)>, String> {
+    let mut blobs = Vec::new();
+
+    for entry in fs::read_dir(path).map_err(|error| error.to_string())? {
+        let entry = entry.map_err(|error| error.to_string())?;
+        let name = entry.file_name().to_string_lossy().into_owned();
+
+        if storage::check_name(&name).is_err() || !entry.path().is_file() {
+            continue;
+        }
+
+        let bytes = fs::read(entry.path()).map_err(|error| format!("{}: {}", name, error))?;
+        blobs.push((name, bytes));
+    }
+
+    blobs.sort();
+
+    Ok(blobs)
+}
+
+fn apply_script(game: &mut Game, script: &str) -> Result<(), String> {
+    for (line_index, full_line) in script.lines().enumerate() {
+        let line = full_line.split('#').next().unwrap_or("").trim();
+        if line.is_empty() {
+            continue;
+        }
+
+        let error = |message: String| format!("line {}: {}", line_index + 1, message);
+
+        let parts: Vec<&str> = line.split_whitespace().collect();
+        match parts.as_slice() {
+            ["place", card, address] => {
+                let card_index = card.parse::<usize>()
+                    .map_err(|_| error(format!("\"{}\" is not a card index", card)))?;
+                let Data::Immeadiate(address) = address.parse::<Data>().map_err(&error)?;
+                let address = address as usize;
+
+                if card_index >= game.cards.len() {
+                    return Err(error(format!("there are only {} cards in the hand",
+                                             game.cards.len())));
+                }
+                if address >= PLAYFIELD_SIZE {
+                    return Err(error(format!("address {:#04X} is off the playfield", address)));
+                }
+
+                //scripts aren't played out over time, so everything is
+                //placed at the start.
+                game::place_card(game, card_index, address, Duration::default());
+
+                if game.cards.is_empty() {
+                    game::deal(game);
+                }
+            }
+            ["scrape", address] => {
+                let Data::Immeadiate(address) = address.parse::<Data>().map_err(&error)?;
+                let address = address as usize;
+
+                if address >= PLAYFIELD_SIZE {
+                    return Err(error(format!("address {:#04X} is off the playfield", address)));
+                }
+
+                game::scrape(game, address);
+            }
+            _ => return Err(error(format!("\"{}\" is not a command", line))),
+        }
+    }
+
+    Ok(())
+}
+
+fn run(game: &mut Game, step_limit: u64) -> (u64, Termination) {
+    game::run(game);
+
+    let mut steps = 0;
+    while let Some(address) = game.executing_address {
+        if steps >= step_limit {
+            return (steps, Termination::StepLimit);
+        }
+
+        game::step(game, address);
+        steps += 1;
+    }
+
+    (steps, Termination::Halted)
+}
+
+fn play_replay(instructions: [Instruction; PLAYFIELD_SIZE],
+               replay: replay::Replay,
+               theme: Theme,
+               blobs: &[(String, Vec<u8>)],
+               keep_screen: bool)
+               -> (Report, Game) {
+    let size = replay.frames.first().map_or(WINDOW_SIZE, |frame| frame.size);
+
+    recording::reset(size);
+    let output = recording::platform();
+    for (name, bytes) in blobs {
+        (output.write_blob)(name, bytes).unwrap_or_else(|error| fail(&error));
+    }
+    let platform = replay::playback_platform(&output);
+
+    let mut game = game::new_game(instructions, size, replay.seed, replay.daily_seed);
+    game.bindings = replay.bindings;
+    game.theme = theme;
+
+    let mut count = 0;
+    let mut termination = Termination::ReplayEnded;
+    for frame in replay.frames {
+        replay::set_frame(&frame);
+        count += 1;
+
+        if recording::run_frame(game::update_and_render, &platform, &mut game, frame.events) {
+            termination = Termination::Quit;
+            break;
+        }
+    }
+
+    (Report {
+        count_name: "frames",
+        count,
+        termination,
+        screen: if keep_screen {
+            Some(recording::screen())
+        } else {
+            None
+        },
+        log: recording::log_lines(),
+    },
+     game)
+}
+
+fn print_text(game: &Game, report: &Report, options: &Options) {
+    for (i, value) in game.registers.iter().enumerate() {
+        if let Some(register) = to_register(i as i32) {
+            println!("{}: {:#04X}", register, value);
+        }
+    }
+
+    println!("seed: {}", game.seed);
+    println!("{}: {}", report.count_name, report.count);
+    println!("termination: {}", report.termination.name());
+
+    if !report.log.is_empty() {
+        println!("log:");
+        for line in report.log.iter() {
+            println!("  {}", line);
+        }
+    }
+
+    if options.profile {
+        println!("profile:");
+        for line in game.profile.report(&game.instructions).lines() {
+            println!("{}", format!("  {}", line).trim_end());
+        }
+    }
+
+    if options.analyse {
+        println!("analysis:");
+        let analysis = analysis::analyse(&game.instructions);
+        for line in analysis.report(&game.instructions).lines() {
+            println!("{}", format!("  {}", line).trim_end());
+        }
+    }
+
+    if options.layers {
+        println!("layers:");
+        for address in layered_addresses(game) {
+            for layer in game.history.layers(address).iter().rev() {
+                println!("  {:#04X}  {:<11}  {}",
+                         address,
+                         layer.instruction.to_string(),
+                         layer.placement.map_or("starting playfield".to_string(),
+                                                |placement| placement.to_string()));
+            }
+        }
+    }
+
+    if let Some(ref screen) = report.screen {
+        println!("screen:\n{}", screen);
+    }
+}
+
+fn print_json(game: &Game, report: &Report, options: &Options) {
+    let registers: Vec<String> = game.registers
+        .iter()
+        .enumerate()
+        .filter_map(|(i, value)| to_register(i as i32).map(|r| format!("\"{}\": {}", r, value)))
+        .collect();
+
+    let screen = match report.screen {
+        Some(ref screen) => format!(", \"screen\": \"{}\"", json_escape(screen)),
+        None => String::new(),
+    };
+
+    let log = if report.log.is_empty() {
+        String::new()
+    } else {
+        let lines: Vec<String> = report.log
+            .iter()
+            .map(|line| format!("\"{}\"", json_escape(line)))
+            .collect();
+
+        format!(", \"log\": [{}]", lines.join(", "))
+    };
+
+    let profile = if options.profile {
+        format!(", \"profile\": {}", profile_json(&game.profile))
+    } else {
+        String::new()
+    };
+
+    let analysis = if options.analyse {
+        format!(", \"analysis\": {}", analysis_json(&analysis::analyse(&game.instructions)))
+    } else {
+        String::new()
+    };
+
+    let layers = if options.layers {
+        format!(", \"layers\": {}", layers_json(game))
+    } else {
+        String::new()
+    };
+
+    println!("{{\"registers\": {{{}}}, \"seed\": {}, \"{}\": {}, \"termination\": \"{}\"{}{}{}{}{}}}",
+             registers.join(", "),
+             game.seed,
+             report.count_name,
+             report.count,
+             report.termination.name(),
+             log,
+             profile,
+             analysis,
+             layers,
+             screen);
+}
+
+fn profile_json(profile: &profile::Profile) -> String {
+    let counts: Vec<String> = profile.counts.iter().map(|count| count.to_string()).collect();
+    let loops: Vec<String> = profile.loops_by_steps()
+        .iter()
+        .map(|l| {
+            format!("{{\"start\": {}, \"end\": {}, \"iterations\": {}, \"steps\": {}}}",
+                    l.start,
+                    l.end,
+                    l.iterations,
+                    l.steps)
+        })
+        .collect();
+
+    format!("{{\"steps\": {}, \"counts\": [{}], \"loops\": [{}]}}",
+            profile.steps,
+            counts.join(", "),
+            loops.join(", "))
+}
+
+fn analysis_json(analysis: &analysis::Analysis) -> String {
+    let list = |addresses: &[usize]| {
+        addresses.iter().map(|address| address.to_string()).collect::<Vec<_>>().join(", ")
+    };
+    let exits: Vec<String> = analysis.exits
+        .iter()
+        .map(|exit| format!("{{\"address\": {}, \"always\": {}}}", exit.address, exit.always))
+        .collect();
+    let loops: Vec<String> = analysis.loops.iter().map(|l| format!("[{}]", list(l))).collect();
+
+    format!("{{\"unreachable\": [{}], \"exits\": [{}], \"loops\": [{}]}}",
+            list(&analysis.unreachable()),
+            exits.join(", "),
+            loops.join(", "))
+}
+
+//the addresses that have been written over at least once.
+fn layered_addresses(game: &Game) -> Vec<usize> {
+    (0..PLAYFIELD_SIZE).filter(|&address| game.history.layers(address).len() > 1).collect()
+}
+
+fn layers_json(game: &Game) -> String {
+    let addresses: Vec<String> = layered_addresses(game)
+        .into_iter()
+        .map(|address| {
+            let layers: Vec<String> = game.history
+                .layers(address)
+                .iter()
+                .rev()
+                .map(|layer| {
+                    format!("{{\"instruction\": \"{}\", \"placement\": {}}}",
+                            layer.instruction,
+                            layer.placement.map_or("null".to_string(), placement_json))
+                })
+                .collect();
+
+            format!("{{\"address\": {}, \"layers\": [{}]}}", address, layers.join(", "))
+        })
+        .collect();
+
+    format!("[{}]", addresses.join(", "))
+}
+
+fn placement_json(placement: history::Placement) -> String {
+    format!("{{\"card\": {}, \"player\": {}, \"turn\": {}, \"time\": {}}}",
+            placement.card,
+            placement.player,
+            placement.turn,
+            placement.time.as_secs_f64())
+}
+
+fn json_escape(text: &str) -> String {
+    let mut result = String::new();
+
+    for character in text.chars() {
+        match character {
+            '"' => result.push_str("\\\""),
+            '\\' => result.push_str("\\\\"),
+            '\n' => result.push_str("\\n"),
+            c if (c as u32) < 0x20 => result.push_str(&format!("\\u{:04x}", c as u32)),
+            c => result.push(c),
+        }
+    }
+
+    result
+}
+
+fn fail(message: &str) -> ! {
+    eprintln!("{}\n\n{}", message, USAGE);
+    process::exit(1);
+}
+
+#[cfg(test)]
+mod tests {
+    use common::*;
+
+    use super::{load_playfield, WINDOW_SIZE};
+
+    #[test]
+    fn loads_a_text_playfield() {
+        let instructions = load_playfield("load 0x02 A\n# a comment\n0x04: add 0x01 B\n").unwrap();
+
+        assert_eq!(instructions[0].to_string(), "load 0x02 A");
+        assert_eq!(instructions[1].to_string(), "NOP");
+        assert_eq!(instructions[4].to_string(), "add  0x01 B");
+    }
+
+    #[test]
+    fn loads_the_playfield_of_a_saved_game() {
+        let mut instructions = [Instruction::NOP; PLAYFIELD_SIZE];
+        instructions[3] = "JNZ 0x01 C".parse().unwrap();
+        let game = game::new_game(instructions, WINDOW_SIZE, DEFAULT_SEED, DEFAULT_SEED);
+
+        let loaded = load_playfield(&save::save(&game)).unwrap();
+
+        assert!(loaded[..] == instructions[..]);
+    }
+}
@@ -0,0 +1,72 @@
+use common::*;
+use common::theme::Theme;
+
+use std::iter;
+
+//Once the game panics it isn't updated again until the library is reloaded,
+//which is what gives a chance to fix whatever went wrong without losing the
+//game. This is shown in its place until then.
+
+#[cfg(feature = "hot-reload")]
+const HINT: &str = "The game is kept as it was before the panic. Rebuild the game library to \
+                    carry on with it, or press Escape to quit.";
+#[cfg(not(feature = "hot-reload"))]
+const HINT: &str = "Press Escape to quit.";
+
+const MAX_WIDTH: i32 = 72;
+
+/// Draws a box in the middle of the window with what the game panicked with.
+pub fn draw(platform: &Platform, theme: &Theme, message: &str) {
+    let size = (platform.size)();
+    let width = MAX_WIDTH.min(size.width - 2).max(3);
+    let text_width = width as usize - 2;
+
+    let mut lines = vec!["The game panicked".to_string(), String::new()];
+    for line in message.lines() {
+        lines.extend(wrap(line, text_width));
+    }
+    lines.push(String::new());
+    lines.extend(wrap(HINT, text_width));
+
+    let height = lines.len() as i32 + 2;
+    let x = ((size.width - width) / 2).max(0);
+    let y = ((size.height - height) / 2).max(0);
+
+    //with a blank row above and below as a border.
+    let blank = String::new();
+    let rows = iter::once(&blank).chain(lines.iter()).chain(iter::once(&blank));
+
+    (platform.set_colors)(theme.status_text, theme.status_failure);
+    for (row_y, text) in (y..).zip(rows) {
+        (platform.print_xy)(x, row_y, &format!(" {:1$.1$} ", text, text_width));
+    }
+    (platform.set_colors)(theme.text, theme.background);
+}
+
+//splits `text` into lines at most `width` characters long, breaking at spaces
+//where it can.
+fn wrap(text: &str, width: usize) -> Vec<String> {
+    let mut lines = Vec::new();
+    let mut line = String::new();
+
+    for word in text.split(' ') {
+        if !line.is_empty() && line.chars().count() + 1 + word.chars().count() > width {
+            lines.push(line);
+            line = String::new();
+        }
+
+        if !line.is_empty() {
+            line.push(' ');
+        }
+        line.push_str(word);
+
+        while line.chars().count() > width {
+            let rest = line.chars().skip(width).collect();
+            lines.push(line.chars().take(width).collect());
+            line = rest;
+        }
+    }
+    lines.push(line);
+
+    lines
+}
@@ -0,0 +1,237 @@
+//Loading the `game` library straight out of `target/debug` and reloading it
+//as soon as its modification time changes means reading it while cargo is
+//still writing it, and the dynamic loader hands back the already loaded
+//library if asked for the same path twice. So each version of the library is
+//copied somewhere else first, and only loaded once it has stopped changing. A
+//new library only replaces the old one after it has loaded, passed the checks
+//and read the game in, and until then the old one keeps running.
+//
+//All of this is only compiled in with the `hot-reload` feature. Without it the
+//`game` crate is linked in directly, see `linked.rs`.
+
+use libloading::{Library, Symbol};
+
+use common::*;
+use common::abi::{self, GameHandle};
+
+use std::env;
+use std::env::consts::{DLL_PREFIX, DLL_SUFFIX};
+use std::fs;
+use std::mem;
+use std::path::{Path, PathBuf};
+use std::process;
+
+use services;
+use status::StatusLine;
+use watcher::Watcher;
+
+pub struct Application {
+    //declared before `_copy` so the library is unloaded before its file is removed.
+    library: Library,
+    _copy: LibraryCopy,
+    //where the library is built to, rather than where the copy is.
+    path: PathBuf,
+}
+
+//a copy of the library in the temp directory, removed again on drop.
+struct LibraryCopy {
+    path: PathBuf,
+}
+
+impl Drop for LibraryCopy {
+    fn drop(&mut self) {
+        let _ = fs::remove_file(&self.path);
+    }
+}
+
+impl Application {
+    //refuses libraries that we can't safely call into.
+    pub fn new(path: &Path) -> Result<Self, String> {
+        let copy = copy_library(path)?;
+        let library = Library::new(&copy.path)
+            .map_err(|error| format!("{}: {}", path.display(), error))?;
+        let app = Application {
+            library,
+            _copy: copy,
+            path: path.to_path_buf(),
+        };
+
+        let abi_version = app.get::<abi::AbiVersion>(abi::ABI_VERSION_SYMBOL)?();
+        if abi_version != abi::ABI_VERSION {
+            return Err(format!("{} has ABI version {} but this binary expects {}. Rebuild \
+                                both with `cargo build`.",
+                               app.path.display(),
+                               abi_version,
+                               abi::ABI_VERSION));
+        }
+
+        //make sure everything is there now rather than partway through a frame.
+        app.get::<abi::GameLayoutHash>(abi::GAME_LAYOUT_HASH_SYMBOL)?;
+        app.get::<abi::CreateGame>(abi::CREATE_GAME_SYMBOL)?;
+        app.get::<abi::UpdateGame>(abi::UPDATE_GAME_SYMBOL)?;
+        app.get::<abi::SaveGame>(abi::SAVE_GAME_SYMBOL)?;
+        app.get::<abi::LoadGame>(abi::LOAD_GAME_SYMBOL)?;
+        app.get::<abi::DropGame>(abi::DROP_GAME_SYMBOL)?;
+        app.get::<abi::SetBindings>(abi::SET_BINDINGS_SYMBOL)?;
+        app.get::<abi::SetTheme>(abi::SET_THEME_SYMBOL)?;
+        app.get::<abi::InvalidateScreen>(abi::INVALIDATE_SCREEN_SYMBOL)?;
+
+        Ok(app)
+    }
+
+    fn get<'a, T>(&'a self, symbol: &[u8]) -> Result<Symbol<'a, T>, String> {
+        unsafe {
+            self.library.get::<T>(symbol).map_err(|error| {
+                format!("{}: {}: {}",
+                        self.path.display(),
+                        String::from_utf8_lossy(&symbol[..symbol.len() - 1]),
+                        error)
+            })
+        }
+    }
+
+    //`new` checked all the symbols are there, so the unwraps below can't fail.
+
+    fn game_layout_hash(&self) -> u64 {
+        let f = self.get::<abi::GameLayoutHash>(abi::GAME_LAYOUT_HASH_SYMBOL).unwrap();
+        f()
+    }
+
+    pub fn create_game(&self,
+                       instructions: [Instruction; PLAYFIELD_SIZE],
+                       size: Size,
+                       seed: Seed,
+                       daily_seed: Seed)
+                       -> GameHandle {
+        let f = self.get::<abi::CreateGame>(abi::CREATE_GAME_SYMBOL).unwrap();
+        f(instructions, size, seed, daily_seed)
+    }
+
+    pub fn update_game(&self,
+                       platform: &Platform,
+                       game: &mut GameHandle,
+                       events: &mut Vec<Event>)
+                       -> Result<bool, String> {
+        let f = self.get::<abi::UpdateGame>(abi::UPDATE_GAME_SYMBOL).unwrap();
+        f(platform, game, events)
+    }
+
+    fn save_game(&self, game: &GameHandle) -> String {
+        let f = self.get::<abi::SaveGame>(abi::SAVE_GAME_SYMBOL).unwrap();
+        f(game)
+    }
+
+    fn load_game(&self, text: &str, size: Size) -> Result<GameHandle, String> {
+        let f = self.get::<abi::LoadGame>(abi::LOAD_GAME_SYMBOL).unwrap();
+        f(text, size)
+    }
+
+    pub fn drop_game(&self, game: GameHandle) {
+        let f = self.get::<abi::DropGame>(abi::DROP_GAME_SYMBOL).unwrap();
+        f(game)
+    }
+
+    pub fn set_bindings(&self, game: &mut GameHandle, bindings: bindings::Bindings) {
+        let f = self.get::<abi::SetBindings>(abi::SET_BINDINGS_SYMBOL).unwrap();
+        f(game, bindings)
+    }
+
+    pub fn set_theme(&self, game: &mut GameHandle, theme: theme::Theme) {
+        let f = self.get::<abi::SetTheme>(abi::SET_THEME_SYMBOL).unwrap();
+        f(game, theme)
+    }
+
+    pub fn invalidate_screen(&self, game: &mut GameHandle) {
+        let f = self.get::<abi::InvalidateScreen>(abi::INVALIDATE_SCREEN_SYMBOL).unwrap();
+        f(game)
+    }
+}
+
+/// Where cargo puts the library, which is next to the executable. Falls back
+/// to where that is relative to the root of the repo.
+pub fn default_lib_path() -> PathBuf {
+    let file_name = format!("{}game{}", DLL_PREFIX, DLL_SUFFIX);
+
+    match env::current_exe() {
+        Ok(ref exe) if exe.parent().is_some() => exe.with_file_name(file_name),
+        _ => Path::new("target").join("debug").join(file_name),
+    }
+}
+
+fn copy_library(path: &Path) -> Result<LibraryCopy, String> {
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    static COPY_COUNT: AtomicUsize = AtomicUsize::new(0);
+
+    let copy_path = env::temp_dir().join(format!("palimpsest-game-{}-{}{}",
+                                                 process::id(),
+                                                 COPY_COUNT.fetch_add(1, Ordering::SeqCst),
+                                                 DLL_SUFFIX));
+
+    fs::copy(path, &copy_path).map_err(|error| format!("{}: {}", path.display(), error))?;
+
+    Ok(LibraryCopy { path: copy_path })
+}
+
+/// Watches for the library being rebuilt and reloads it.
+pub struct Reloader {
+    watcher: Watcher,
+}
+
+impl Reloader {
+    pub fn new(path: &Path) -> Self {
+        Reloader { watcher: Watcher::new(path) }
+    }
+
+    /// Call once a frame. How any reload went is shown on `status`. Returns
+    /// true if the library was replaced.
+    pub fn update(&mut self,
+                  app: &mut Application,
+                  game: &mut GameHandle,
+                  size: Size,
+                  status: &mut StatusLine)
+                  -> bool {
+        if !self.watcher.poll() {
+            return false;
+        }
+
+        match reload(app, game, size) {
+            Ok(message) => {
+                services::log(&message);
+                status.success(message);
+
+                true
+            }
+            Err(error) => {
+                let message = format!("Reload failed, still running the old library: {}", error);
+                services::log(&message);
+                status.failure(message);
+
+                false
+            }
+        }
+    }
+}
+
+/// Loads the current version of the library, and moves `game` over to it. If
+/// anything goes wrong, `app` and `game` are left as they were. Returns what
+/// happened, for the status line.
+fn reload(app: &mut Application, game: &mut GameHandle, size: Size) -> Result<String, String> {
+    let new_app = Application::new(&app.path)?;
+
+    let saved = app.save_game(game);
+    let new_game = new_app.load_game(&saved, size)
+        .map_err(|error| format!("the new library couldn't read the game: {}", error))?;
+
+    let old_game = mem::replace(game, new_game);
+    app.drop_game(old_game);
+
+    let message = if app.game_layout_hash() == new_app.game_layout_hash() {
+        "Reloaded the game library"
+    } else {
+        "Reloaded the game library, and moved the game to the new layout"
+    };
+
+    *app = new_app;
+
+    Ok(message.to_string())
+}
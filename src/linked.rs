@@ -0,0 +1,46 @@
+//Without the `hot-reload` feature the `game` crate is linked in like any other
+//crate, so the binary runs from anywhere without `libgame.so` next to it. This
+//has the same interface as the `Application` in `hot_reload.rs`, so `main`
+//doesn't need to care which one it is using.
+
+use game;
+
+use common::*;
+use common::abi::GameHandle;
+
+pub struct Application;
+
+impl Application {
+    pub fn create_game(&self,
+                       instructions: [Instruction; PLAYFIELD_SIZE],
+                       size: Size,
+                       seed: Seed,
+                       daily_seed: Seed)
+                       -> GameHandle {
+        game::create_game(instructions, size, seed, daily_seed)
+    }
+
+    pub fn update_game(&self,
+                       platform: &Platform,
+                       game: &mut GameHandle,
+                       events: &mut Vec<Event>)
+                       -> Result<bool, String> {
+        game::update_game(platform, game, events)
+    }
+
+    pub fn drop_game(&self, game: GameHandle) {
+        game::drop_game(game)
+    }
+
+    pub fn set_bindings(&self, game: &mut GameHandle, bindings: bindings::Bindings) {
+        game::set_bindings(game, bindings)
+    }
+
+    pub fn set_theme(&self, game: &mut GameHandle, theme: theme::Theme) {
+        game::set_theme(game, theme)
+    }
+
+    pub fn invalidate_screen(&self, game: &mut GameHandle) {
+        game::invalidate_screen(game)
+    }
+}
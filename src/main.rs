@@ -1,131 +1,430 @@
+#[cfg(feature = "hot-reload")]
 extern crate libloading;
+#[cfg(feature = "bearlibterminal")]
 extern crate bear_lib_terminal;
 extern crate common;
+#[cfg(not(feature = "hot-reload"))]
+extern crate game;
 
-use libloading::Library;
+use common::*;
+use common::bindings::Bindings;
+use common::theme::Theme;
+
+use std::fs::File;
+use std::io::{BufWriter, Read};
+use std::path::PathBuf;
+use std::thread;
+use std::time::{Instant, SystemTime, UNIX_EPOCH};
+
+use settings_file::SettingsFile;
+use status::StatusLine;
+
+#[cfg(feature = "bearlibterminal")]
+mod bearlib;
+mod ansi;
+mod crash;
+#[cfg(feature = "hot-reload")]
+mod hot_reload;
+#[cfg(not(feature = "hot-reload"))]
+mod linked;
+mod services;
+mod settings_file;
+mod status;
+mod watcher;
+
+/// Everything the main loop needs from a place to draw and read input from.
+/// `platform` is what gets handed to the `game` crate.
+pub struct Backend {
+    pub platform: Platform,
+    pub open: fn(&str, i32, i32),
+    pub read_events: fn(&mut Vec<Event>),
+    pub clear_screen: fn(),
+    pub refresh: fn(),
+    pub close: fn(),
+}
 
-use bear_lib_terminal::terminal::{self, config, Event, KeyCode, state};
-use bear_lib_terminal::Color;
-use bear_lib_terminal::geometry::{Point, Rect, Size};
+#[cfg(feature = "bearlibterminal")]
+fn default_backend() -> Backend {
+    bearlib::backend()
+}
 
-use std::mem;
+#[cfg(not(feature = "bearlibterminal"))]
+fn default_backend() -> Backend {
+    ansi::backend()
+}
 
-use common::*;
+struct Options {
+    backend: Backend,
+    record_path: Option<String>,
+    replay_path: Option<String>,
+    seed: Option<Seed>,
+    daily: bool,
+    //only set if `--bindings` or `--theme` was passed, since then the file has
+    //to exist.
+    bindings_path: Option<PathBuf>,
+    theme_path: Option<PathBuf>,
+    #[cfg(feature = "hot-reload")]
+    lib_path: PathBuf,
+}
 
-const LIB_PATH: &'static str = "./target/debug/libgame.so";
+fn parse_args() -> Options {
+    let mut options = Options {
+        backend: default_backend(),
+        record_path: None,
+        replay_path: None,
+        seed: None,
+        daily: false,
+        bindings_path: None,
+        theme_path: None,
+        #[cfg(feature = "hot-reload")]
+        lib_path: hot_reload::default_lib_path(),
+    };
 
-struct Application {
-    library: Library,
+    let mut args = std::env::args().skip(1);
+
+    while let Some(arg) = args.next() {
+        match arg.as_ref() {
+            "--backend" => {
+                options.backend = match args.next().as_ref().map(|s| s.as_ref()) {
+                    Some("ansi") => ansi::backend(),
+                    #[cfg(feature = "bearlibterminal")]
+                    Some("bearlibterminal") => bearlib::backend(),
+                    other => {
+                        fail(&format!("unknown backend {:?}, expected one of: {}",
+                                      other.unwrap_or(""),
+                                      BACKEND_NAMES))
+                    }
+                }
+            }
+            "--record" => {
+                options.record_path =
+                    Some(args.next().unwrap_or_else(|| fail("--record needs a file name")))
+            }
+            "--replay" => {
+                options.replay_path =
+                    Some(args.next().unwrap_or_else(|| fail("--replay needs a file name")))
+            }
+            "--seed" => {
+                let value = args.next().unwrap_or_else(|| fail("--seed needs a number"));
+                options.seed = Some(value.parse()
+                    .unwrap_or_else(|_| fail(&format!("\"{}\" is not a seed", value))));
+            }
+            "--daily" => options.daily = true,
+            "--bindings" => {
+                options.bindings_path =
+                    Some(PathBuf::from(args.next()
+                        .unwrap_or_else(|| fail("--bindings needs a file name"))))
+            }
+            "--theme" => {
+                options.theme_path =
+                    Some(PathBuf::from(args.next()
+                        .unwrap_or_else(|| fail("--theme needs a file name"))))
+            }
+            #[cfg(feature = "hot-reload")]
+            "--lib" => {
+                options.lib_path =
+                    PathBuf::from(args.next().unwrap_or_else(|| fail("--lib needs a file name")))
+            }
+            other => fail(&format!("unknown argument \"{}\"", other)),
+        }
+    }
+
+    options
+}
+
+fn fail(message: &str) -> ! {
+    eprintln!("{}", message);
+    std::process::exit(1);
 }
 
-impl Application {
-    fn new() -> Self {
-        let library = Library::new(LIB_PATH).unwrap_or_else(|error| panic!("{}", error));
+//different every run, unless one is asked for.
+fn random_seed() -> Seed {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|duration| duration.subsec_nanos() ^ duration.as_secs() as Seed)
+        .unwrap_or(DEFAULT_SEED)
+}
 
-        Application { library: library }
+//opens the settings file at `path` if one was passed, which has to exist, or
+//at `default_path` otherwise.
+fn settings_file<T>(path: Option<PathBuf>,
+                    default_path: &str,
+                    parse: fn(&str) -> (T, Vec<String>))
+                    -> SettingsFile<T> {
+    if let Some(ref path) = path {
+        if !path.exists() {
+            fail(&format!("{}: no such file", path.display()));
+        }
     }
 
-    fn new_game(&self, instructions: [Instruction; PLAYFIELD_SIZE], size: common::Size) -> Game {
-        unsafe {
-            let f = self.library
-                .get::<fn([Instruction; PLAYFIELD_SIZE], common::Size) -> Game>(b"new_game\0")
-                .unwrap();
-            f(instructions, size)
+    SettingsFile::new(&path.unwrap_or_else(|| PathBuf::from(default_path)), parse)
+}
+
+//reads `file` at startup, when not being able to is fatal but problems with
+//what is in it aren't.
+fn load_settings<T>(file: &SettingsFile<T>, status: &mut StatusLine) -> T {
+    let (settings, problems) = file.load().unwrap_or_else(|error| fail(&error));
+    if !problems.is_empty() {
+        for problem in problems.iter() {
+            eprintln!("{}: {}", file.path().display(), problem);
         }
+        status.failure(file.summary(&problems));
     }
 
-    fn update_and_render(&self, platform: &Platform, game: &mut Game, events: &Vec<Event>) -> bool {
-        unsafe {
-            let f = self.library
-                .get::<fn(&Platform, &mut Game, &Vec<Event>) -> bool>(b"update_and_render\0")
-                .unwrap();
-            f(platform, game, events)
+    settings
+}
+
+//reads `file` again after it changed, saying how it went on `status`.
+fn reload_settings<T>(file: &SettingsFile<T>, status: &mut StatusLine) -> Option<T> {
+    match file.load() {
+        Ok((settings, problems)) => {
+            if problems.is_empty() {
+                status.success(format!("Reloaded {}", file.path().display()));
+            } else {
+                status.failure(file.summary(&problems));
+            }
+
+            Some(settings)
+        }
+        Err(error) => {
+            status.failure(format!("Couldn't reload {}", error));
+            None
         }
     }
 }
 
-fn main() {
-    terminal::open("Palimpsest Processor", 80, 30);
-    terminal::set(config::Window::empty().resizeable(true));
-    terminal::set(vec![config::InputFilter::Group {
-                           group: config::InputFilterGroup::Keyboard,
-                           both: false,
-                       },
-                       config::InputFilter::Group {
-                           group: config::InputFilterGroup::Mouse,
-                           both: false,
-                       }]);
+fn load_replay(path: &str) -> replay::Replay {
+    let mut text = String::new();
 
+    File::open(path)
+        .and_then(|mut file| file.read_to_string(&mut text))
+        .map_err(|error| error.to_string())
+        .and_then(|_| replay::parse(&text))
+        .unwrap_or_else(|error| fail(&format!("{}: {}", path, error)))
+}
 
+//the most updates run before the screen is shown again, when they fall behind.
+const MAX_CATCH_UP_TICKS: u32 = 4;
 
-    let mut app = Application::new();
+#[cfg(feature = "bearlibterminal")]
+const BACKEND_NAMES: &str = "bearlibterminal, ansi";
+#[cfg(not(feature = "bearlibterminal"))]
+const BACKEND_NAMES: &str = "ansi";
 
-    let mut game = app.new_game(common::get_instructions(), size());
+fn main() {
+    let options = parse_args();
+    let backend = options.backend;
 
-    let mut last_modified = std::fs::metadata(LIB_PATH).unwrap().modified().unwrap();
+    let replay = options.replay_path.as_ref().map(|path| load_replay(path));
 
-    let platform = Platform {
-        print_xy: terminal::print_xy,
-        clear: clear,
-        size: size,
-        mouse_position: mouse_position,
-        clicks: terminal::state::mouse::clicks,
-        key_pressed: key_pressed,
-        set_colors: set_colors,
+    let daily_seed = match replay {
+        Some(ref replay) => replay.daily_seed,
+        None => common::daily_seed(SystemTime::now()),
     };
+    let seed = match replay {
+        Some(ref replay) => replay.seed,
+        None if options.daily => daily_seed,
+        None => options.seed.unwrap_or_else(random_seed),
+    };
+
+    let mut status = StatusLine::new();
+
+    let mut bindings_file = settings_file(options.bindings_path,
+                                          settings_file::DEFAULT_BINDINGS_PATH,
+                                          Bindings::parse);
+    let mut theme_file = settings_file(options.theme_path,
+                                       settings_file::DEFAULT_THEME_PATH,
+                                       Theme::parse);
+
+    //a replay is played back with the bindings it was recorded with. The theme
+    //only changes how things look, so it always comes from the file.
+    let bindings = match replay {
+        Some(ref replay) => replay.bindings.clone(),
+        None => load_settings(&bindings_file, &mut status),
+    };
+    let mut theme = load_settings(&theme_file, &mut status);
+
+    let mut replay_frames = replay.map(|replay| replay.frames).unwrap_or_default().into_iter();
+
+    let mut recorder = options.record_path.as_ref().map(|path| {
+        File::create(path)
+            .and_then(|file| {
+                replay::Recorder::new(BufWriter::new(file), seed, daily_seed, &bindings)
+            })
+            .unwrap_or_else(|error| fail(&format!("{}: {}", path, error)))
+    });
+
+    #[cfg(feature = "hot-reload")]
+    let mut reloader = hot_reload::Reloader::new(&options.lib_path);
+    #[cfg(feature = "hot-reload")]
+    let mut app = hot_reload::Application::new(&options.lib_path)
+        .unwrap_or_else(|error| fail(&error));
+    #[cfg(not(feature = "hot-reload"))]
+    let app = linked::Application;
+
+    (backend.open)("Palimpsest Processor", 80, 30);
+
+    let live_platform = backend.platform;
+    let playback_platform = replay::playback_platform(&live_platform);
+
+    let mut game = app.create_game(common::get_instructions(),
+                                   (live_platform.size)(),
+                                   seed,
+                                   daily_seed);
+    app.set_bindings(&mut game, bindings);
+    app.set_theme(&mut game, theme);
 
     let mut events = Vec::new();
 
-    app.update_and_render(&platform, &mut game, &mut events);
+    let mut first_frame = true;
+
+    //what the game panicked with, until the library is reloaded.
+    let mut panic_message: Option<String> = None;
 
-    terminal::refresh();
+    let mut status_shown = false;
 
-    loop {
-        events.clear();
+    //when the next update is due. Updates are run one after another to catch
+    //up if they fall behind, then the screen is shown once for all of them.
+    let mut next_tick = Instant::now();
 
-        while let Some(event) = terminal::read_event() {
-            events.push(event);
+    'frames: loop {
+        if !first_frame {
+            (backend.read_events)(&mut events);
+        }
+        first_frame = false;
+
+        let now = Instant::now();
+        if now < next_tick {
+            //there's nothing to do until then. Input that arrives meanwhile
+            //is kept for the next update.
+            thread::sleep(next_tick - now);
+            continue;
         }
 
-        terminal::clear(None);
+        let mut ticks = 0;
+        while next_tick <= now && ticks < MAX_CATCH_UP_TICKS {
+            next_tick += clock::TICK;
+            ticks += 1;
+        }
+        //after a long stall, like the process being suspended, carry on from
+        //now rather than rushing through everything that was missed.
+        if next_tick <= now {
+            next_tick = now + clock::TICK;
+        }
+
+        for _ in 0..ticks {
+            if panic_message.is_some() {
+                let quit = events.iter().any(|event| {
+                    matches!(*event,
+                             Event::Close |
+                             Event::KeyPressed { key: KeyCode::Escape, ctrl: _, shift: _ })
+                });
+                if quit {
+                    break 'frames;
+                }
+
+                //the game isn't updated, so there is nothing to catch up on,
+                //and nothing of it to show under the panic message.
+                events.clear();
+                (backend.clear_screen)();
+                break;
+            }
 
-        if app.update_and_render(&platform, &mut game, &mut events) {
-            //quit requested
-            break;
+            let platform = match replay_frames.next() {
+                Some(frame) => {
+                    //only closing the window is let through while a replay plays.
+                    if events.contains(&Event::Close) {
+                        break 'frames;
+                    }
+
+                    replay::set_frame(&frame);
+                    events = frame.events;
+
+                    &playback_platform
+                }
+                None => &live_platform,
+            };
+
+            //what the game reads is only known once it has been updated, so
+            //the frame is recorded after that.
+            let platform = match recorder {
+                Some(_) => replay::recording_platform(platform),
+                None => *platform,
+            };
+            let captured = recorder.as_ref().map(|_| replay::capture(&platform, &events));
+
+            let result = app.update_game(&platform, &mut game, &mut events);
+            events.clear();
+            clock::advance();
+
+            if let (Some(recording), Some(mut frame)) = (recorder.as_mut(), captured) {
+                frame.reads = replay::take_reads();
+
+                if let Err(error) = recording.record(frame) {
+                    eprintln!("stopped recording: {}", error);
+                    recorder = None;
+                }
+            }
+
+            match result {
+                //quit requested
+                Ok(true) => break 'frames,
+                Ok(false) => {}
+                Err(message) => panic_message = Some(message),
+            }
         }
 
-        terminal::refresh();
+        #[cfg(feature = "hot-reload")]
+        {
+            if reloader.update(&mut app, &mut game, (live_platform.size)(), &mut status) {
+                panic_message = None;
+            }
+        }
 
-        if let Ok(Ok(modified)) = std::fs::metadata(LIB_PATH).map(|m| m.modified()) {
-            if modified > last_modified {
-                drop(app);
-                app = Application::new();
-                last_modified = modified;
+        if bindings_file.changed() {
+            //neither recordings nor replays keep track of changing bindings.
+            if recorder.is_some() || replay_frames.len() > 0 {
+                status.failure(format!("{} changed, but bindings aren't reloaded while \
+                                        recording or replaying",
+                                       bindings_file.path().display()));
+            } else if let Some(bindings) = reload_settings(&bindings_file, &mut status) {
+                app.set_bindings(&mut game, bindings);
             }
         }
 
-    }
-    terminal::close();
-}
+        if theme_file.changed() {
+            if let Some(new_theme) = reload_settings(&theme_file, &mut status) {
+                theme = new_theme;
+                app.set_theme(&mut game, theme);
+            }
+        }
 
-fn clear(area: Option<common::Rect>) {
-    unsafe { terminal::clear(mem::transmute::<Option<common::Rect>, Option<Rect>>(area)) };
-}
+        if let Some(ref message) = panic_message {
+            crash::draw(&live_platform, &theme, message);
+        }
 
-fn size() -> common::Size {
-    unsafe { mem::transmute::<Size, common::Size>(state::size()) }
-}
+        //the game only draws what changed since its last frame, so once
+        //the status line is gone it has to draw the whole screen again.
+        let status_was_shown = status_shown;
+        status_shown = status.draw(&live_platform, &theme);
+        if status_was_shown && !status_shown {
+            app.invalidate_screen(&mut game);
+        }
 
-fn mouse_position() -> common::Point {
-    unsafe { mem::transmute::<Point, common::Point>(state::mouse::position()) }
-}
+        (backend.refresh)();
+    }
+    app.drop_game(game);
 
-fn key_pressed(key: common::KeyCode) -> bool {
-    terminal::state::key_pressed(unsafe { mem::transmute::<common::KeyCode, KeyCode>(key) })
-}
+    (backend.close)();
 
-fn set_colors(fg: common::Color, bg: common::Color) {
-    terminal::set_colors(unsafe { mem::transmute::<common::Color, Color>(fg) },
-                         unsafe { mem::transmute::<common::Color, Color>(bg) });
+    //the overlay is gone along with the window, so say what happened here.
+    if let Some(message) = panic_message {
+        eprintln!("the game panicked: {}", message);
+    }
 
+    if let Some(recorder) = recorder {
+        if let Err(error) = recorder.finish() {
+            eprintln!("couldn't finish recording: {}", error);
+        }
+    }
 }
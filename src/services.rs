@@ -0,0 +1,62 @@
+//The storage and logging parts of `Platform`, which are the same whichever
+//backend draws the window. Blobs are files in `DATA_DIR`, and the log is
+//appended to `LOG_PATH`, since anything printed to the terminal would end up
+//on top of the game. Both are relative to the directory the game was started
+//from, like the bindings file.
+
+use common::storage;
+
+use std::fs::{self, OpenOptions};
+use std::io::{ErrorKind, Write};
+use std::path::PathBuf;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+pub const DATA_DIR: &str = "palimpsest-data";
+pub const LOG_PATH: &str = "palimpsest.log";
+
+pub fn read_blob(name: &str) -> Result<Option<Vec<u8>>, String> {
+    storage::check_name(name)?;
+    let path = blob_path(name);
+
+    match fs::read(&path) {
+        Ok(bytes) => Ok(Some(bytes)),
+        Err(ref error) if error.kind() == ErrorKind::NotFound => Ok(None),
+        Err(error) => Err(format!("{}: {}", path.display(), error)),
+    }
+}
+
+pub fn write_blob(name: &str, bytes: &[u8]) -> Result<(), String> {
+    storage::check_name(name)?;
+    let path = blob_path(name);
+
+    //written next to it and then moved over it, so a crash part way through
+    //leaves the old blob rather than half of the new one. The suffix goes on
+    //the whole name, since replacing an extension would give `a.txt` and
+    //`a.dat` the same temporary file.
+    let temp_path = blob_path(&format!("{}.partial", name));
+
+    fs::create_dir_all(DATA_DIR)
+        .and_then(|_| fs::write(&temp_path, bytes))
+        .and_then(|_| fs::rename(&temp_path, &path))
+        .map_err(|error| format!("{}: {}", path.display(), error))
+}
+
+/// Appends `line` to the log, with the time in seconds since the Unix epoch
+/// in front of it. There is nowhere left to report it if that fails, so any
+/// error is ignored.
+pub fn log(line: &str) {
+    let seconds = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|duration| duration.as_secs())
+        .unwrap_or(0);
+
+    let _ = OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(LOG_PATH)
+        .and_then(|mut file| writeln!(file, "{} {}", seconds, line));
+}
+
+fn blob_path(name: &str) -> PathBuf {
+    PathBuf::from(DATA_DIR).join(name)
+}
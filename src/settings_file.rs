@@ -0,0 +1,67 @@
+//The key bindings and the theme are read from files at startup, and again
+//whenever a file changes, so they can be tried out without restarting. See
+//`common::bindings` and `common::theme` for the formats.
+
+use std::fs::File;
+use std::io::{self, Read};
+use std::path::{Path, PathBuf};
+
+use watcher::Watcher;
+
+/// Where the bindings are read from unless `--bindings` says otherwise.
+pub const DEFAULT_BINDINGS_PATH: &str = "bindings.txt";
+/// Where the theme is read from unless `--theme` says otherwise.
+pub const DEFAULT_THEME_PATH: &str = "theme.txt";
+
+pub struct SettingsFile<T> {
+    path: PathBuf,
+    watcher: Watcher,
+    //returns what was read along with any problems with it, like
+    //`Bindings::parse`.
+    parse: fn(&str) -> (T, Vec<String>),
+}
+
+impl<T> SettingsFile<T> {
+    pub fn new(path: &Path, parse: fn(&str) -> (T, Vec<String>)) -> Self {
+        SettingsFile {
+            path: path.to_path_buf(),
+            watcher: Watcher::new(path),
+            parse,
+        }
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// Reads the settings, along with any problems with them. A missing file
+    /// means the defaults.
+    pub fn load(&self) -> Result<(T, Vec<String>), String> {
+        let mut text = String::new();
+
+        match File::open(&self.path).and_then(|mut file| file.read_to_string(&mut text)) {
+            Ok(_) => Ok((self.parse)(&text)),
+            Err(ref error) if error.kind() == io::ErrorKind::NotFound => Ok((self.parse)("")),
+            Err(error) => Err(format!("{}: {}", self.path.display(), error)),
+        }
+    }
+
+    /// Returns true once each time the file has changed and stopped changing.
+    pub fn changed(&mut self) -> bool {
+        self.watcher.poll()
+    }
+
+    /// One line describing `problems`, for the status line.
+    pub fn summary(&self, problems: &[String]) -> String {
+        match problems.len() {
+            0 => format!("Loaded {}", self.path.display()),
+            1 => format!("{}: {}", self.path.display(), problems[0]),
+            count => {
+                format!("{}: {} (and {} more problems)",
+                        self.path.display(),
+                        problems[0],
+                        count - 1)
+            }
+        }
+    }
+}
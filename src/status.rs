@@ -0,0 +1,67 @@
+use common::*;
+use common::theme::Theme;
+
+use std::time::{Duration, Instant};
+
+const SUCCESS_DURATION: Duration = Duration::from_secs(3);
+const FAILURE_DURATION: Duration = Duration::from_secs(10);
+
+/// A line at the bottom of the window saying how something that happened
+/// outside the game went, like the game library being reloaded or the
+/// bindings file being read. Only the latest message is shown.
+pub struct StatusLine {
+    message: Option<Message>,
+}
+
+struct Message {
+    text: String,
+    failed: bool,
+    until: Instant,
+}
+
+impl StatusLine {
+    pub fn new() -> Self {
+        StatusLine { message: None }
+    }
+
+    pub fn success(&mut self, text: String) {
+        self.show(text, false, SUCCESS_DURATION);
+    }
+
+    pub fn failure(&mut self, text: String) {
+        self.show(text, true, FAILURE_DURATION);
+    }
+
+    fn show(&mut self, text: String, failed: bool, duration: Duration) {
+        self.message = Some(Message {
+            text,
+            failed,
+            until: Instant::now() + duration,
+        });
+    }
+
+    /// Call once a frame, after the game has drawn. Returns true if there was
+    /// anything to draw.
+    pub fn draw(&mut self, platform: &Platform, theme: &Theme) -> bool {
+        if self.message.as_ref().is_some_and(|message| Instant::now() >= message.until) {
+            self.message = None;
+        }
+
+        if let Some(ref message) = self.message {
+            let size = (platform.size)();
+            let width = size.width.max(0) as usize;
+
+            let background = if message.failed {
+                theme.status_failure
+            } else {
+                theme.status_success
+            };
+
+            (platform.set_colors)(theme.status_text, background);
+            (platform.print_xy)(0, size.height - 1, &format!("{:1$.1$}", message.text, width));
+            (platform.set_colors)(theme.text, theme.background);
+        }
+
+        self.message.is_some()
+    }
+}
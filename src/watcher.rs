@@ -0,0 +1,75 @@
+//Editors and cargo both write files in more than one go, so reading a file as
+//soon as its modification time changes can mean reading half of it. A
+//`Watcher` waits for the file to stop changing first.
+
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant, SystemTime};
+
+//how long a file has to go without changing before it is reported.
+const DEBOUNCE: Duration = Duration::from_millis(300);
+
+/// Notices when a file has changed, waiting for it to stop changing before
+/// saying so.
+pub struct Watcher {
+    path: PathBuf,
+    loaded: Option<Stamp>,
+    pending: Option<(Stamp, Instant)>,
+}
+
+#[derive(Clone, Copy, PartialEq)]
+struct Stamp {
+    modified: SystemTime,
+    len: u64,
+}
+
+fn current_stamp(path: &Path) -> Option<Stamp> {
+    let metadata = fs::metadata(path).ok()?;
+
+    Some(Stamp {
+        modified: metadata.modified().ok()?,
+        len: metadata.len(),
+    })
+}
+
+impl Watcher {
+    /// Starts out treating the file as it is now as already loaded.
+    pub fn new(path: &Path) -> Self {
+        Watcher {
+            path: path.to_path_buf(),
+            loaded: current_stamp(path),
+            pending: None,
+        }
+    }
+
+    /// Returns true once per new version of the file, after it has gone
+    /// `DEBOUNCE` without changing.
+    pub fn poll(&mut self) -> bool {
+        let stamp = match current_stamp(&self.path) {
+            Some(stamp) => stamp,
+            //probably part way through being replaced.
+            None => return false,
+        };
+
+        if Some(stamp) == self.loaded {
+            self.pending = None;
+            return false;
+        }
+
+        match self.pending {
+            Some((pending, since)) if pending == stamp => {
+                if since.elapsed() >= DEBOUNCE {
+                    self.loaded = Some(stamp);
+                    self.pending = None;
+                    true
+                } else {
+                    false
+                }
+            }
+            _ => {
+                self.pending = Some((stamp, Instant::now()));
+                false
+            }
+        }
+    }
+}